@@ -1,16 +1,136 @@
 use bevy::prelude::*;
 
+use rand::Rng;
+
+use crate::{input::Action, settings::SettingKind};
+
+use crate::constants::{
+    CHASER_REPATH_SECS, COIN_FADE_SECS, DASH_COOLDOWN, DASH_DURATION, DASH_TRAIL_FADE_SECS, DASH_TRAIL_INTERVAL,
+    FLOATING_TEXT_SECS, FREEZE_SECS, KNOCKBACK_SECS, POWERUP_ROOM_CHANCE, ROOM_SIZE, SENTRY_FIRE_INTERVAL,
+    SENTRY_PROJECTILE, SENTRY_ROOM_CHANCE, SHRINK_SECS, SLOW_TIME_SECS, SPAWN_MARGIN, TURRET_FIRE_INTERVAL,
+    TURRET_PROJECTILE, TURRET_ROOM_CHANCE,
+};
+
 #[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum GameState {
     #[default]
     Menu,
     Playing,
+    Paused,
+    Settings,
     GameOver,
 }
 
 #[derive(Component)]
 pub struct Player;
 
+// The player's current velocity, carried between frames by the momentum model,
+// and the last direction moved in, which a dash follows when no key is held
+#[derive(Component)]
+pub struct PlayerMotion {
+    pub velocity: Vec2,
+    pub facing: Vec2,
+}
+
+impl Default for PlayerMotion {
+    fn default() -> Self {
+        Self {
+            velocity: Vec2::ZERO,
+            facing: Vec2::Y,
+        }
+    }
+}
+
+// Hits the player can still take; the run ends when the last one is lost
+#[derive(Component)]
+pub struct Health {
+    pub lives: u32,
+}
+
+// Pushes the player away from whatever hit them, overriding movement until the timer finishes
+#[derive(Component)]
+pub struct Knockback {
+    pub velocity: Vec2,
+    pub timer: Timer,
+}
+
+impl Knockback {
+    pub fn new(velocity: Vec2) -> Self {
+        Self {
+            velocity,
+            timer: Timer::from_seconds(KNOCKBACK_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// Time until the player can dash again; ready once the timer has finished
+#[derive(Component)]
+pub struct DashCooldown {
+    pub timer: Timer,
+}
+
+impl DashCooldown {
+    pub fn ready() -> Self {
+        let mut timer = Timer::from_seconds(DASH_COOLDOWN, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self { timer }
+    }
+}
+
+// Present while the player is mid-dash
+#[derive(Component)]
+pub struct Dashing {
+    pub direction: Vec2,
+    pub timer: Timer,
+    pub trail_timer: Timer,
+}
+
+impl Dashing {
+    pub fn new(direction: Vec2) -> Self {
+        Self {
+            direction,
+            timer: Timer::from_seconds(DASH_DURATION, TimerMode::Once),
+            trail_timer: Timer::from_seconds(DASH_TRAIL_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+// Coins and chasers pass through the player until the timer finishes
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+}
+
+impl Invulnerable {
+    pub fn new(secs: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(secs, TimerMode::Once),
+        }
+    }
+
+    // Restarts the window at `secs` unless more than that is already left, so a short
+    // window such as a dash's never cuts a longer one short
+    pub fn extend(&mut self, secs: f32) {
+        if self.timer.remaining_secs() < secs {
+            *self = Self::new(secs);
+        }
+    }
+}
+
+// Fading afterimage left behind by a dash
+#[derive(Component)]
+pub struct DashTrail {
+    pub timer: Timer,
+}
+
+impl DashTrail {
+    pub fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(DASH_TRAIL_FADE_SECS, TimerMode::Once),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Wall;
 
@@ -21,12 +141,90 @@ pub struct MainCamera;
 pub struct Coin {
     pub velocity: Vec2,
     pub stationary_timer: Timer,
+    pub speed: f32,
+    pub mass: f32,
+    pub launch_direction: Vec2,
+}
+
+// Grid cell of the room the coin is currently in
+#[derive(Component)]
+pub struct CoinRoom {
+    pub cell: IVec2,
+}
+
+// Keeps the coin inside its room by treating passages as walls
+#[derive(Component)]
+pub struct RoomBound;
+
+// Marks a coin outside the simulation region; it is frozen until the player comes back
+#[derive(Component)]
+pub struct Sleeping;
+
+// One dot of the line telegraphing a stationary coin's launch direction
+#[derive(Component)]
+pub struct TelegraphDot {
+    pub index: u32,
+}
+
+// Fades the coin out once it has hit a wall `remaining` more times
+#[derive(Component)]
+pub struct BounceLimit {
+    pub remaining: u32,
+}
+
+// Multiplies the coin's velocity by `factor` on every wall bounce
+#[derive(Component)]
+pub struct BounceSpeedup {
+    pub factor: f32,
+}
+
+// Starts the coin fading out once it has been moving for the timer's duration
+#[derive(Component)]
+pub struct Lifetime {
+    pub timer: Timer,
+}
+
+// A coin on its way out; it cannot hurt the player and despawns when the timer finishes
+#[derive(Component)]
+pub struct Fading {
+    pub timer: Timer,
+}
+
+impl Fading {
+    pub fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(COIN_FADE_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// Steers the coin's velocity toward the player, in radians per second
+#[derive(Component)]
+pub struct Homing {
+    pub steering: f32,
+}
+
+// Forks the coin into `fragments` smaller coins when it hits a wall
+#[derive(Component)]
+pub struct Splitting {
+    pub fragments: u32,
+    pub generations: u32,
+}
+
+// Continuously turns the coin's velocity so it travels in circles
+#[derive(Component)]
+pub struct Orbiting {
+    pub angular_speed: f32,
 }
 
 #[derive(Component)]
 pub struct Room {
     pub position: Vec2,
     pub exits: Vec<Direction>,
+    // Areas relative to the room centre where coins may spawn; empty means anywhere inside the walls
+    pub spawn_zones: Vec<Rect>,
+    // GameTimer seconds when the player first entered the room
+    pub first_visited_at: Option<f32>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -38,7 +236,7 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn to_vec2(&self) -> Vec2 {
+    pub fn to_vec2(self) -> Vec2 {
         match self {
             Direction::North => Vec2::new(0.0, 1.0),
             Direction::South => Vec2::new(0.0, -1.0),
@@ -47,7 +245,7 @@ impl Direction {
         }
     }
 
-    pub fn opposite(&self) -> Direction {
+    pub fn opposite(self) -> Direction {
         match self {
             Direction::North => Direction::South,
             Direction::South => Direction::North,
@@ -61,6 +259,125 @@ impl Direction {
     }
 }
 
+// Layout a generated room is built from: which hazards it holds and where coins may spawn
+#[derive(Clone, Copy, PartialEq)]
+pub enum RoomTemplate {
+    Open,
+    // A single turret in the centre aiming at the player
+    Turret,
+    // Two sentries in opposite corners firing bursts
+    Sentries,
+}
+
+impl RoomTemplate {
+    pub fn random(rng: &mut impl Rng) -> RoomTemplate {
+        let roll: f32 = rng.gen();
+        if roll < TURRET_ROOM_CHANCE {
+            RoomTemplate::Turret
+        } else if roll < TURRET_ROOM_CHANCE + SENTRY_ROOM_CHANCE {
+            RoomTemplate::Sentries
+        } else {
+            RoomTemplate::Open
+        }
+    }
+
+    // Areas relative to the room centre where coins may spawn; empty means anywhere
+    pub fn spawn_zones(self) -> Vec<Rect> {
+        let inner = ROOM_SIZE / 2.0 - SPAWN_MARGIN;
+        match self {
+            RoomTemplate::Open => Vec::new(),
+            // Keep random coins in the corners, away from the turret
+            RoomTemplate::Turret => vec![
+                Rect::new(-inner, -inner, -inner / 2.0, -inner / 2.0),
+                Rect::new(inner / 2.0, -inner, inner, -inner / 2.0),
+                Rect::new(-inner, inner / 2.0, -inner / 2.0, inner),
+                Rect::new(inner / 2.0, inner / 2.0, inner, inner),
+            ],
+            // Keep random coins in the middle, away from the sentries
+            RoomTemplate::Sentries => vec![Rect::new(-inner / 2.0, -inner / 2.0, inner / 2.0, inner / 2.0)],
+        }
+    }
+
+    // Emitters the room holds, positioned relative to the room centre
+    pub fn emitters(self) -> Vec<(Vec2, Emitter)> {
+        let corner = ROOM_SIZE / 2.0 - 60.0;
+        match self {
+            RoomTemplate::Open => Vec::new(),
+            RoomTemplate::Turret => vec![(
+                Vec2::ZERO,
+                Emitter::new(
+                    EmitterPattern::Aimed { count: 3, spread: 0.5 },
+                    TURRET_PROJECTILE,
+                    TURRET_FIRE_INTERVAL,
+                ),
+            )],
+            RoomTemplate::Sentries => vec![
+                (
+                    Vec2::new(-corner, corner),
+                    Emitter::new(EmitterPattern::Burst { count: 8 }, SENTRY_PROJECTILE, SENTRY_FIRE_INTERVAL),
+                ),
+                (
+                    Vec2::new(corner, -corner),
+                    Emitter::new(EmitterPattern::Burst { count: 8 }, SENTRY_PROJECTILE, SENTRY_FIRE_INTERVAL),
+                ),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum EmitterPattern {
+    // A fan of `count` coins spread over `spread` radians, centred on the player
+    Aimed { count: u32, spread: f32 },
+    // A ring of `count` coins, rotated a little further with every shot
+    Burst { count: u32 },
+}
+
+// Stationary hazard that fires coins; spawned as a child of its room
+#[derive(Component)]
+pub struct Emitter {
+    pub pattern: EmitterPattern,
+    // Name of the coin kind in coins.ron that the emitter fires
+    pub projectile: &'static str,
+    pub fire_timer: Timer,
+    pub shots: u32,
+}
+
+impl Emitter {
+    pub fn new(pattern: EmitterPattern, projectile: &'static str, interval: f32) -> Self {
+        Self {
+            pattern,
+            projectile,
+            fire_timer: Timer::from_seconds(interval, TimerMode::Repeating),
+            shots: 0,
+        }
+    }
+}
+
+// Enemy that follows the room graph toward the player
+#[derive(Component)]
+pub struct Chaser {
+    pub speed: f32,
+    // How quickly the chaser turns toward where it wants to go, from 0 to 1
+    pub aggression: f32,
+    pub velocity: Vec2,
+    // Rooms still to pass through on the way to the player's room
+    pub path: Vec<IVec2>,
+    pub repath_timer: Timer,
+}
+
+impl Chaser {
+    pub fn new(speed: f32, aggression: f32) -> Self {
+        Self {
+            speed,
+            aggression,
+            velocity: Vec2::ZERO,
+            path: Vec::new(),
+            repath_timer: Timer::from_seconds(CHASER_REPATH_SECS, TimerMode::Repeating),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct GameOverText;
 
@@ -72,10 +389,293 @@ pub enum ButtonAction {
     Start,
     Exit,
     Restart,
+    CycleMode,
+    Resume,
+    QuitToMenu,
+    OpenSettings,
+    Back,
+    ChangeSetting(SettingKind),
+    Rebind(Action),
+    ResetBindings,
+}
+
+// Button pressed by the Confirm action on screens without a pointer
+#[derive(Component)]
+pub struct DefaultButton;
+
+// Settings screen, despawned when leaving the settings state
+#[derive(Component)]
+pub struct SettingsMenu;
+
+// Text showing the current value of a setting
+#[derive(Component)]
+pub struct SettingValue {
+    pub kind: SettingKind,
+}
+
+// Text listing the bindings of an action on the settings screen
+#[derive(Component)]
+pub struct BindingLabel {
+    pub action: Action,
 }
 
+// Pause menu overlay, despawned when leaving the pause state
+#[derive(Component)]
+pub struct PauseMenu;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PowerUpKind {
+    Shield,
+    SlowTime,
+    Shrink,
+    Repel,
+    Freeze,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::Shield,
+        PowerUpKind::SlowTime,
+        PowerUpKind::Shrink,
+        PowerUpKind::Repel,
+        PowerUpKind::Freeze,
+    ];
+
+    // Rolls whether a new room holds a power-up, and which
+    pub fn roll(rng: &mut impl Rng) -> Option<PowerUpKind> {
+        if rng.gen::<f32>() < POWERUP_ROOM_CHANCE {
+            Some(PowerUpKind::ALL[rng.gen_range(0..PowerUpKind::ALL.len())])
+        } else {
+            None
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::SlowTime => "Slow",
+            PowerUpKind::Shrink => "Shrink",
+            PowerUpKind::Repel => "Repel",
+            PowerUpKind::Freeze => "Freeze",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PowerUpKind::Shield => Color::rgb(0.3, 0.6, 1.0),
+            PowerUpKind::SlowTime => Color::rgb(0.6, 0.4, 1.0),
+            PowerUpKind::Shrink => Color::rgb(0.3, 0.9, 0.5),
+            PowerUpKind::Repel => Color::rgb(1.0, 0.6, 0.2),
+            PowerUpKind::Freeze => Color::rgb(0.7, 0.95, 1.0),
+        }
+    }
+}
+
+// Pickup lying in a room; spawned as a child of its room
+#[derive(Component)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+}
+
+// Absorbs the next hit, then breaks
+#[derive(Component)]
+pub struct Shield;
+
+// Coins move at reduced speed until the timer finishes
+#[derive(Component)]
+pub struct SlowTime {
+    pub timer: Timer,
+}
+
+impl SlowTime {
+    pub fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(SLOW_TIME_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// The player is smaller until the timer finishes
+#[derive(Component)]
+pub struct Shrunk {
+    pub timer: Timer,
+}
+
+impl Shrunk {
+    pub fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(SHRINK_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// Coins stand still until the timer finishes
+#[derive(Component)]
+pub struct Frozen {
+    pub timer: Timer,
+}
+
+impl Frozen {
+    pub fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(FREEZE_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// Coin currently within NEAR_MISS_MARGIN of the player; scores once it gets away without a hit
+#[derive(Component)]
+pub struct Grazing;
+
+// Coin that has already scored a near miss; stepping back into its margin earns nothing more
+#[derive(Component)]
+pub struct NearMissed;
+
+// What a coin's graze does this frame
+#[derive(Debug, PartialEq, Eq)]
+pub enum GrazeChange {
+    None,
+    Start,
+    Cancel, // Ended by a hit or a dash, without scoring
+    Score,
+}
+
+impl Grazing {
+    // Nothing counts while the player cannot be hit, whether dashing through coins or
+    // recovering from the hit that ended the graze, or while the coins are frozen
+    pub fn change(close: bool, grazing: bool, near_missed: bool, invulnerable: bool, frozen: bool) -> GrazeChange {
+        let safe = invulnerable || frozen;
+        if grazing && safe {
+            GrazeChange::Cancel
+        } else if grazing && !close {
+            GrazeChange::Score
+        } else if close && !grazing && !near_missed && !safe {
+            GrazeChange::Start
+        } else {
+            GrazeChange::None
+        }
+    }
+}
+
+// World-space text that drifts upward and fades, used for points popping up
+#[derive(Component)]
+pub struct FloatingText {
+    pub timer: Timer,
+}
+
+impl FloatingText {
+    pub fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(FLOATING_TEXT_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// Collectible worth GOLD_COIN_POINTS; spawned as a child of its room
+#[derive(Component)]
+pub struct GoldCoin;
+
+// HUD icon for an active power-up effect
+#[derive(Component)]
+pub struct EffectIcon {
+    pub kind: PowerUpKind,
+}
+
+// UI node the map of discovered rooms is drawn into
+#[derive(Component)]
+pub struct MinimapRoot;
+
+// HUD text showing the survival time
+#[derive(Component)]
+pub struct TimerText;
+
+// HUD text showing the current score
+#[derive(Component)]
+pub struct ScoreText;
+
+// HUD text showing how many rooms have been explored
+#[derive(Component)]
+pub struct RoomsText;
+
+// HUD text showing the combo multiplier
+#[derive(Component)]
+pub struct ComboText;
+
+// Game over text listing the best runs of the session
+#[derive(Component)]
+pub struct LeaderboardText;
+
+// HUD text showing the lives left
+#[derive(Component)]
+pub struct LivesText;
+
+// HUD text showing whether the dash is ready
+#[derive(Component)]
+pub struct DashText;
+
+// Text showing the selected game mode on the main menu
+#[derive(Component)]
+pub struct ModeLabel;
+
 #[derive(Component)]
 pub struct MenuCleanup;
 
 #[derive(Component)]
-pub struct Cleanup; 
\ No newline at end of file
+pub struct Cleanup; 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{DASH_INVULNERABLE_SECS, HIT_INVULNERABLE_SECS};
+    use std::time::Duration;
+
+    #[test]
+    fn dash_after_a_hit_keeps_the_hits_invulnerability() {
+        let mut invulnerable = Invulnerable::new(HIT_INVULNERABLE_SECS);
+        invulnerable.timer.tick(Duration::from_secs_f32(0.2));
+        let remaining = invulnerable.timer.remaining_secs();
+
+        invulnerable.extend(DASH_INVULNERABLE_SECS);
+        assert_eq!(invulnerable.timer.remaining_secs(), remaining);
+
+        // Once less than a dash's worth is left, the dash restarts the window
+        invulnerable.timer.tick(Duration::from_secs_f32(remaining - 0.05));
+        invulnerable.extend(DASH_INVULNERABLE_SECS);
+        assert_eq!(invulnerable.timer.remaining_secs(), DASH_INVULNERABLE_SECS);
+    }
+
+    #[test]
+    fn a_coin_scores_one_near_miss_at_most() {
+        let (mut grazing, mut near_missed) = (false, false);
+        let mut scored = 0;
+        // In and out of the same coin's margin three times
+        for close in [true, false, true, false, true, false] {
+            match Grazing::change(close, grazing, near_missed, false, false) {
+                GrazeChange::Start => grazing = true,
+                GrazeChange::Score => {
+                    (grazing, near_missed) = (false, true);
+                    scored += 1;
+                }
+                GrazeChange::Cancel => grazing = false,
+                GrazeChange::None => {}
+            }
+        }
+        assert_eq!(scored, 1);
+    }
+
+    #[test]
+    fn a_hit_cancels_the_graze_without_scoring() {
+        assert_eq!(Grazing::change(true, true, false, true, false), GrazeChange::Cancel);
+        assert_eq!(Grazing::change(true, false, false, true, false), GrazeChange::None);
+        // The coin can still be grazed once the player is vulnerable again
+        assert_eq!(Grazing::change(true, false, false, false, false), GrazeChange::Start);
+    }
+
+    #[test]
+    fn freezing_cancels_the_graze_without_scoring() {
+        // Whether or not the player is still close when the freeze starts
+        assert_eq!(Grazing::change(true, true, false, false, true), GrazeChange::Cancel);
+        assert_eq!(Grazing::change(false, true, false, false, true), GrazeChange::Cancel);
+        assert_eq!(Grazing::change(true, false, false, false, true), GrazeChange::None);
+    }
+}
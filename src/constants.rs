@@ -2,14 +2,137 @@ use bevy::prelude::*;
 
 pub const PLAYER_SIZE: Vec2 = Vec2::new(32.0, 32.0);
 pub const PLAYER_SPEED: f32 = 300.0;
+pub const PLAYER_ACCELERATION: f32 = 900.0;
+pub const PLAYER_DECELERATION: f32 = 1200.0;
+pub const DASH_DISTANCE: f32 = 160.0;
+pub const DASH_DURATION: f32 = 0.12;
+pub const DASH_COOLDOWN: f32 = 1.5;
+pub const DASH_INVULNERABLE_SECS: f32 = 0.25; // Counted from the start of the dash
+pub const DASH_TRAIL_INTERVAL: f32 = 0.02;
+pub const DASH_TRAIL_FADE_SECS: f32 = 0.3;
+pub const DASH_TRAIL_COLOR: Color = Color::rgba(0.4, 0.7, 1.0, 0.6);
+
+// Lives and hit reaction
+pub const CLASSIC_LIVES: u32 = 3;
+pub const WEIGHTY_LIVES: u32 = 4;
+pub const HIT_INVULNERABLE_SECS: f32 = 1.5;
+pub const HIT_FLASH_INTERVAL: f32 = 0.1;
+pub const KNOCKBACK_SPEED: f32 = 600.0;
+pub const KNOCKBACK_SECS: f32 = 0.15;
+
+// Score
+pub const SCORE_PER_SECOND: f32 = 10.0;
+pub const SCORE_PER_NEW_ROOM: u32 = 250;
+pub const GOLD_COIN_POINTS: u32 = 100;
+pub const GOLD_COINS_PER_ROOM: u32 = 3; // At most; each room rolls between none and this many
+pub const GOLD_COIN_SIZE: Vec2 = Vec2::new(18.0, 18.0);
+pub const GOLD_COIN_COLOR: Color = Color::rgb(1.0, 0.8, 0.1);
+pub const LEADERBOARD_SIZE: usize = 5;
+
+// Near misses and combo
+pub const NEAR_MISS_MARGIN: f32 = 16.0; // Gap between coin and player that counts as close
+pub const NEAR_MISS_POINTS: u32 = 25;
+pub const COMBO_MAX: u32 = 8;
+pub const COMBO_DECAY_SECS: f32 = 2.5; // The multiplier drops by one after this long without a near miss
+pub const NEAR_MISS_SLOWMO_SPEED: f32 = 0.35;
+pub const NEAR_MISS_SLOWMO_SECS: f32 = 0.25; // Real time, not slowed game time
+pub const FLOATING_TEXT_SECS: f32 = 0.8;
+pub const FLOATING_TEXT_RISE: f32 = 60.0;
+
+// Minimap
+pub const MINIMAP_SIZE: f32 = 160.0;
+pub const MINIMAP_CELL_SIZE: f32 = 22.0;
+pub const MINIMAP_FULL_MAX_CELL_SIZE: f32 = 60.0;
+pub const MINIMAP_ROOM_FILL: f32 = 0.7; // Fraction of a cell a room covers, the rest is the gap between rooms
+pub const MINIMAP_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+pub const MINIMAP_FULL_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.85);
+pub const MINIMAP_ROOM_COLOR: Color = Color::rgb(0.6, 0.6, 0.6);
+pub const MINIMAP_CURRENT_COLOR: Color = Color::rgb(0.3, 0.7, 1.0);
+pub const MINIMAP_FOG_COLOR: Color = Color::rgba(0.4, 0.4, 0.4, 0.3);
+
+// Power-ups
+pub const POWERUP_ROOM_CHANCE: f32 = 0.3;
+pub const POWERUP_SIZE: Vec2 = Vec2::new(20.0, 20.0);
+pub const SHIELD_BREAK_INVULNERABLE_SECS: f32 = 1.0;
+pub const SLOW_TIME_SECS: f32 = 5.0;
+pub const SLOW_TIME_SCALE: f32 = 0.5;
+pub const SHRINK_SECS: f32 = 8.0;
+pub const SHRINK_SCALE: f32 = 0.6;
+pub const FREEZE_SECS: f32 = 3.0;
+pub const REPEL_RADIUS: f32 = 250.0;
+pub const REPEL_SPEED: f32 = 400.0;
 pub const WALL_THICKNESS: f32 = 20.0;
 pub const ROOM_SIZE: f32 = 600.0;
 pub const PASSAGE_WIDTH: f32 = 100.0;
 pub const COIN_SIZE: Vec2 = Vec2::new(24.0, 24.0);
-pub const COIN_SPEED: f32 = 200.0;
 pub const COIN_SPAWN_CHANCE: f32 = 0.02;
+pub const SPAWN_MARGIN: f32 = 50.0; // Keep spawned coins this far inside the room edge
+pub const SPAWN_ATTEMPTS: u32 = 16;
+pub const SPAWN_MIN_PLAYER_DISTANCE: f32 = 150.0;
+pub const SPAWN_MIN_COIN_SPACING: f32 = 48.0;
+pub const SPAWN_ENTRY_GRACE_SECS: f32 = 2.0;
+pub const SPAWN_ACTIVE_RING: i32 = 1;
+pub const FORMATION_INTERVAL_SECS: f32 = 15.0;
+pub const FORMATION_MIN_INTERVAL_SECS: f32 = 6.0;
+pub const FORMATION_INTERVAL_STEP: f32 = 1.5; // Seconds shaved off the interval per difficulty level
+pub const FORMATION_SPACING: f32 = 50.0;
+pub const FORMATION_STAGGER_SECS: f32 = 0.12;
+pub const FORMATION_RING_COINS: usize = 12;
+pub const FORMATION_RING_RADIUS: f32 = 120.0;
+pub const FORMATION_SPIRAL_COINS: usize = 16;
+pub const FORMATION_SPIRAL_RADIUS: f32 = 30.0;
+pub const FORMATION_SPIRAL_GROWTH: f32 = 8.0;
+pub const FORMATION_SPIRAL_TURN: f32 = 0.5; // Radians between consecutive spiral coins
+pub const FORMATION_WALL_GAP: usize = 3; // Missing coins in a wall formation
 pub const COIN_STATIONARY_TIME: f32 = 3.0;
+pub const TELEGRAPH_DOTS: u32 = 6;
+pub const TELEGRAPH_DOT_SIZE: f32 = 4.0;
+pub const TELEGRAPH_DOT_SPACING: f32 = 12.0;
+pub const TELEGRAPH_EMPTY_ALPHA: f32 = 0.2;
+pub const WALL_GRID_CELL_SIZE: f32 = 200.0;
+pub const COIN_GRID_CELL_SIZE: f32 = 64.0;
+pub const TRAJECTORY_PREVIEW_SECS: f32 = 1.0;
+pub const TRAJECTORY_PREVIEW_STEP: f32 = 1.0 / 60.0;
+pub const TRAJECTORY_PREVIEW_RANGE: f32 = 400.0;
+pub const COIN_DEFINITIONS_PATH: &str = "assets/coins.ron";
+pub const CHASER_DEFINITIONS_PATH: &str = "assets/chasers.ron";
+pub const DIFFICULTY_STEP_SECS: f32 = 30.0;
+pub const COIN_MAX_SPEED: f32 = 800.0; // Cap for coins that speed up on every bounce
+pub const COIN_FADE_SECS: f32 = 0.75;
+pub const SPLIT_FRAGMENT_SCALE: f32 = 0.75;
+pub const SPLIT_SPREAD: f32 = 0.8; // Radians between the outermost fragments
 pub const HOLE_CHANCE: f32 = 0.99;
 pub const ROOM_GENERATION_DISTANCE: i32 = 2;
-// Add a small buffer to prevent coins from popping in/out at screen edges
-pub const VISIBILITY_BUFFER: f32 = 100.0; 
\ No newline at end of file
+pub const CHASER_SIZE: Vec2 = Vec2::new(28.0, 28.0);
+pub const CHASER_COLOR: Color = Color::rgb(0.9, 0.2, 0.6);
+pub const CHASER_STEERING: f32 = 8.0; // Turn rate at full aggression
+pub const CHASER_REPATH_SECS: f32 = 0.5;
+pub const CHASER_PASSAGE_LEAD: f32 = 40.0; // How far past a passage the chaser aims
+pub const CHASER_SPAWN_INTERVAL: f32 = 20.0;
+pub const CHASER_MIN_DIFFICULTY: u32 = 2;
+pub const CHASER_MAX_ALIVE: usize = 3;
+pub const TURRET_ROOM_CHANCE: f32 = 0.15;
+pub const SENTRY_ROOM_CHANCE: f32 = 0.1;
+pub const TURRET_FIRE_INTERVAL: f32 = 3.0;
+pub const SENTRY_FIRE_INTERVAL: f32 = 4.5;
+pub const EMITTER_IDLE_COLOR: Color = Color::rgb(0.5, 0.1, 0.1);
+pub const EMITTER_CHARGED_COLOR: Color = Color::rgb(1.0, 0.3, 0.2);
+pub const EMITTER_SIZE: Vec2 = Vec2::new(36.0, 36.0);
+pub const EMITTER_RANGE: f32 = 700.0; // Emitters only fire when the player is this close
+pub const EMITTER_TELEGRAPH_SECS: f32 = 1.0; // Emitters glow for this long before firing
+pub const EMITTER_BURST_TURN: f32 = 0.2; // Radians a burst emitter rotates between shots
+pub const TURRET_PROJECTILE: &str = "basic"; // Coin kind from coins.ron that turrets fire
+pub const SENTRY_PROJECTILE: &str = "basic"; // Coin kind from coins.ron that sentries fire
+// Coins keep moving within SIMULATION_ACTIVE_RING rooms of the player, sleep
+// out to SIMULATION_SLEEP_RING rooms and are despawned beyond that
+pub const SIMULATION_ACTIVE_RING: i32 = 2;
+pub const SIMULATION_SLEEP_RING: i32 = 4; 
+// Settings file, inside the user's config directory
+pub const SETTINGS_DIR: &str = "dodgecoin";
+pub const SETTINGS_FILE: &str = "settings.ron";
+pub const UI_SCALES: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+// Input bindings and saved binding profiles
+pub const INPUT_PRESS_THRESHOLD: f32 = 0.5; // Analog value at which an action counts as pressed
+pub const INPUT_CAPTURE_THRESHOLD: f32 = 0.7; // How far a stick must move to be picked up as a new binding
+pub const INPUT_PROFILE_COUNT: usize = 3;
+pub const MOVE_DEADZONE: f32 = 0.2; // Stick movement shorter than this is ignored
@@ -1,2 +1,4 @@
 pub const BALL_BYTES: &[u8] = include_bytes!("../assets/ball.png");
-pub const COIN_BYTES: &[u8] = include_bytes!("../assets/coin.png"); 
\ No newline at end of file
+pub const COIN_BYTES: &[u8] = include_bytes!("../assets/coin.png");
+pub const COIN_DEFINITIONS: &str = include_str!("../assets/coins.ron");
+pub const CHASER_DEFINITIONS: &str = include_str!("../assets/chasers.ron");
@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+
+// Sent when a coin or chaser touches the player; `source` is where the hit came from
+#[derive(Event)]
+pub struct PlayerHit {
+    pub source: Vec2,
+}
+
+// Sent when a coin passes close by the player without hitting them
+#[derive(Event)]
+pub struct NearMiss {
+    pub position: Vec2,
+    pub points: u32,
+    pub multiplier: u32,
+}
+
+// Sent by `coin_movement` whenever a coin reflects off a wall
+#[derive(Event)]
+pub struct CoinBounced {
+    pub coin: Entity,
+    pub position: Vec2,
+}
@@ -0,0 +1,344 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::constants::{INPUT_PRESS_THRESHOLD, INPUT_PROFILE_COUNT, MOVE_DEADZONE};
+
+// Logical inputs the game responds to, whatever device they come from
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Dash,
+    Pause,
+    Confirm,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Dash,
+        Action::Pause,
+        Action::Confirm,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Dash => "Dash",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+        }
+    }
+
+    // Actions the menus can't be used without, so they always keep at least one binding
+    pub fn required(self) -> bool {
+        matches!(self, Action::Pause | Action::Confirm)
+    }
+}
+
+// A physical input that can trigger an action
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    // One direction of a gamepad axis, read as an analog value from 0 to 1
+    GamepadAxis { axis: GamepadAxisType, positive: bool },
+}
+
+impl Binding {
+    pub fn label(self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::GamepadButton(button) => format!("Pad {button:?}"),
+            Binding::GamepadAxis { axis, positive } => {
+                format!("Pad {axis:?}{}", if positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+// Bindings for every action; an action may have any number of them
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        use Binding::{GamepadAxis as Axis, GamepadButton as Pad, Key, Mouse};
+        use GamepadAxisType::{LeftStickX, LeftStickY};
+
+        let bindings = BTreeMap::from([
+            (Action::MoveUp, vec![
+                Key(KeyCode::W),
+                Key(KeyCode::Up),
+                Pad(GamepadButtonType::DPadUp),
+                Axis { axis: LeftStickY, positive: true },
+            ]),
+            (Action::MoveDown, vec![
+                Key(KeyCode::S),
+                Key(KeyCode::Down),
+                Pad(GamepadButtonType::DPadDown),
+                Axis { axis: LeftStickY, positive: false },
+            ]),
+            (Action::MoveLeft, vec![
+                Key(KeyCode::A),
+                Key(KeyCode::Left),
+                Pad(GamepadButtonType::DPadLeft),
+                Axis { axis: LeftStickX, positive: false },
+            ]),
+            (Action::MoveRight, vec![
+                Key(KeyCode::D),
+                Key(KeyCode::Right),
+                Pad(GamepadButtonType::DPadRight),
+                Axis { axis: LeftStickX, positive: true },
+            ]),
+            (Action::Dash, vec![
+                Key(KeyCode::Space),
+                Key(KeyCode::ShiftLeft),
+                Mouse(MouseButton::Right),
+                Pad(GamepadButtonType::South),
+            ]),
+            (Action::Pause, vec![Key(KeyCode::Escape), Pad(GamepadButtonType::Start)]),
+            (Action::Confirm, vec![
+                Key(KeyCode::Return),
+                Key(KeyCode::Space),
+                Pad(GamepadButtonType::South),
+            ]),
+        ]);
+        Self { bindings }
+    }
+}
+
+impl ActionMap {
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // Adds the binding to the action, or removes it if the action already has it. Returns false,
+    // changing nothing, when that would leave a required action with no bindings.
+    pub fn toggle(&mut self, action: Action, binding: Binding) -> bool {
+        let bindings = self.bindings.entry(action).or_default();
+        if let Some(index) = bindings.iter().position(|&existing| existing == binding) {
+            if action.required() && bindings.len() == 1 {
+                return false;
+            }
+            bindings.remove(index);
+        } else {
+            bindings.push(binding);
+        }
+        true
+    }
+}
+
+// Saved binding profiles and which one is in use
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub active_profile: usize,
+    pub profiles: Vec<ActionMap>,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            active_profile: 0,
+            profiles: vec![ActionMap::default(); INPUT_PROFILE_COUNT],
+        }
+    }
+}
+
+impl InputSettings {
+    // Fills in missing profiles, keeps the active one in range and gives required actions
+    // back their default bindings if they have none, for files edited by hand
+    pub fn normalize(&mut self) {
+        self.profiles.resize_with(INPUT_PROFILE_COUNT, ActionMap::default);
+        self.active_profile = self.active_profile.min(INPUT_PROFILE_COUNT - 1);
+
+        let defaults = ActionMap::default();
+        for profile in &mut self.profiles {
+            for action in Action::ALL.into_iter().filter(|action| action.required()) {
+                if profile.bindings(action).is_empty() {
+                    profile.bindings.insert(action, defaults.bindings(action).to_vec());
+                }
+            }
+        }
+    }
+
+    pub fn active(&self) -> &ActionMap {
+        &self.profiles[self.active_profile]
+    }
+
+    pub fn active_mut(&mut self) -> &mut ActionMap {
+        &mut self.profiles[self.active_profile]
+    }
+}
+
+// How strongly each action is held this frame, filled in from the active bindings by `read_actions`
+#[derive(Resource, Default)]
+pub struct ActionState {
+    values: HashMap<Action, f32>,
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn update(&mut self, values: HashMap<Action, f32>) {
+        let pressed: HashSet<Action> = values
+            .iter()
+            .filter(|(_, &value)| value >= INPUT_PRESS_THRESHOLD)
+            .map(|(&action, _)| action)
+            .collect();
+        self.just_pressed = pressed.difference(&self.pressed).copied().collect();
+        self.pressed = pressed;
+        self.values = values;
+    }
+
+    // From 0 to 1, where buttons and keys are always one or the other
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    // Direction and strength from the four move actions, no longer than 1. Anything inside the
+    // deadzone reads as zero and the rest of the range is stretched to fill it, so a stick
+    // pushed part of the way moves the player at part speed while keys always give full speed.
+    pub fn movement(&self) -> Vec2 {
+        let raw = Vec2::new(
+            self.value(Action::MoveRight) - self.value(Action::MoveLeft),
+            self.value(Action::MoveUp) - self.value(Action::MoveDown),
+        );
+        let length = raw.length();
+        if length <= MOVE_DEADZONE {
+            return Vec2::ZERO;
+        }
+        let strength = (length.min(1.0) - MOVE_DEADZONE) / (1.0 - MOVE_DEADZONE);
+        raw * (strength / length)
+    }
+}
+
+// The candidate nearest to `from` that lies in `direction`, for moving menu focus with a pad or keys.
+// Candidates off to the side count as further away than ones straight ahead.
+pub fn nearest_in_direction<T>(
+    from: Vec2,
+    direction: Vec2,
+    candidates: impl IntoIterator<Item = (T, Vec2)>,
+) -> Option<T> {
+    candidates
+        .into_iter()
+        .filter_map(|(item, position)| {
+            let offset = position - from;
+            let ahead = offset.dot(direction);
+            let aside = offset.perp_dot(direction).abs();
+            (ahead > 1.0).then_some((item, ahead + aside * 2.0))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(item, _)| item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_adds_and_removes_bindings() {
+        let mut map = ActionMap::default();
+        let binding = Binding::Key(KeyCode::K);
+
+        assert!(map.toggle(Action::Dash, binding));
+        assert!(map.bindings(Action::Dash).contains(&binding));
+        assert!(map.toggle(Action::Dash, binding));
+        assert!(!map.bindings(Action::Dash).contains(&binding));
+    }
+
+    #[test]
+    fn required_actions_keep_their_last_binding() {
+        let mut map = ActionMap::default();
+        let escape = Binding::Key(KeyCode::Escape);
+        let start = Binding::GamepadButton(GamepadButtonType::Start);
+
+        assert!(map.toggle(Action::Pause, escape));
+        assert!(!map.toggle(Action::Pause, start));
+        assert_eq!(map.bindings(Action::Pause), [start]);
+
+        // Other actions can be left unbound
+        for binding in ActionMap::default().bindings(Action::Dash) {
+            assert!(map.toggle(Action::Dash, *binding));
+        }
+        assert!(map.bindings(Action::Dash).is_empty());
+    }
+
+    #[test]
+    fn normalize_restores_missing_required_bindings() {
+        let mut settings = InputSettings::default();
+        settings.active_mut().bindings.remove(&Action::Confirm);
+        settings.normalize();
+        assert_eq!(settings.active(), &ActionMap::default());
+    }
+
+    #[test]
+    fn just_pressed_lasts_one_update() {
+        let mut state = ActionState::default();
+        state.update(HashMap::from([(Action::Dash, 1.0)]));
+        assert!(state.just_pressed(Action::Dash));
+
+        state.update(HashMap::from([(Action::Dash, 1.0)]));
+        assert!(!state.just_pressed(Action::Dash));
+
+        state.update(HashMap::from([(Action::Dash, 0.0)]));
+        state.update(HashMap::from([(Action::Dash, 1.0)]));
+        assert!(state.just_pressed(Action::Dash));
+    }
+
+    #[test]
+    fn movement_has_a_deadzone_and_scales_with_the_stick() {
+        let mut state = ActionState::default();
+        state.update(HashMap::from([(Action::MoveRight, MOVE_DEADZONE * 0.5)]));
+        assert_eq!(state.movement(), Vec2::ZERO);
+
+        state.update(HashMap::from([(Action::MoveRight, 0.6)]));
+        let partial = state.movement().length();
+        assert!(partial > 0.0 && partial < 0.6);
+
+        // Two keys at once still give full speed, not more
+        state.update(HashMap::from([(Action::MoveUp, 1.0), (Action::MoveLeft, 1.0)]));
+        assert!((state.movement().length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn focus_moves_to_the_nearest_button_in_line() {
+        let buttons = [
+            ("above", Vec2::new(0.0, -60.0)),
+            ("below", Vec2::new(0.0, 60.0)),
+            ("below and aside", Vec2::new(200.0, 50.0)),
+            ("far below", Vec2::new(0.0, 120.0)),
+        ];
+        assert_eq!(nearest_in_direction(Vec2::ZERO, Vec2::Y, buttons), Some("below"));
+        assert_eq!(nearest_in_direction(Vec2::ZERO, Vec2::NEG_Y, buttons), Some("above"));
+        assert_eq!(nearest_in_direction(Vec2::ZERO, Vec2::NEG_X, buttons), None);
+    }
+
+    #[test]
+    fn action_map_round_trips_through_ron() {
+        let mut settings = InputSettings::default();
+        settings.active_mut().toggle(Action::Pause, Binding::Mouse(MouseButton::Middle));
+
+        let source = ron::to_string(&settings).unwrap();
+        assert_eq!(ron::from_str::<InputSettings>(&source).unwrap(), settings);
+    }
+}
@@ -1,14 +1,27 @@
-use bevy::prelude::*;
+// Bevy systems routinely take complex queries and many parameters
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+use bevy::{ecs::schedule::common_conditions::any_with_component, input::InputSystem, prelude::*};
 mod components;
 mod constants;
 mod systems;
 mod resources;
 mod embedded_assets;
+mod events;
+mod input;
+mod minimap;
+mod pathfinding;
+mod physics;
+mod settings;
+mod spawning;
 
 use components::*;
 //use constants::*;
 use systems::*;
 use resources::*;
+use events::*;
+use input::ActionState;
+use settings::UserSettings;
 
 fn main() {
     App::new()
@@ -22,27 +35,142 @@ fn main() {
         }))
         .init_resource::<GameAssets>()
         .init_resource::<GameTimer>()
+        .init_resource::<GameMode>()
+        .init_resource::<CoinArchetypes>()
+        .init_resource::<ChaserConfig>()
+        .init_resource::<RoomNavigation>()
+        .insert_resource(UserSettings::load())
+        .init_resource::<SettingsReturn>()
+        .init_resource::<ActionState>()
+        .init_resource::<Rebinding>()
+        .init_resource::<MenuFocus>()
+        .init_resource::<SpawnRules>()
+        .init_resource::<CurrentRoom>()
+        .init_resource::<SimulationRegion>()
+        .init_resource::<CoinPhysics>()
+        .init_resource::<SpawnDirector>()
+        .init_resource::<CoinTimeScale>()
+        .init_resource::<Score>()
+        .init_resource::<Leaderboard>()
+        .init_resource::<Combo>()
+        .init_resource::<RunStats>()
+        .init_resource::<MinimapSettings>()
+        .init_resource::<SlowMotion>()
+        .add_event::<CoinBounced>()
+        .add_event::<PlayerHit>()
+        .add_event::<NearMiss>()
         .add_state::<GameState>()
-        .add_systems(Startup, load_assets)
+        .add_systems(Startup, (load_assets, load_coin_archetypes, load_chaser_config))
+        .add_systems(PreUpdate, read_actions.after(InputSystem))
         .add_systems(OnEnter(GameState::Menu), setup_menu)
         .add_systems(OnExit(GameState::Menu), cleanup_menu)
-        .add_systems(OnEnter(GameState::Playing), (setup, setup_ui))
+        // Resuming from the pause menu re-enters Playing with the run still in place
+        .add_systems(
+            OnEnter(GameState::Playing),
+            (setup, setup_ui).run_if(not(any_with_component::<Player>())),
+        )
+        .add_systems(OnEnter(GameState::Paused), setup_pause_menu)
+        .add_systems(OnExit(GameState::Paused), cleanup_pause_menu)
+        .add_systems(OnEnter(GameState::Settings), setup_settings_menu)
+        .add_systems(OnExit(GameState::Settings), cleanup_settings_menu)
         .add_systems(OnEnter(GameState::GameOver), show_game_over)
         .add_systems(OnExit(GameState::GameOver), cleanup_system)
         .add_systems(
             Update,
             (
-                handle_buttons,
+                // Menu input is handled before a new binding is captured, so the press that
+                // starts a capture is not bound and the one that ends it does nothing else
+                (
+                    (navigate_menu_focus, confirm_menu_choice)
+                        .chain()
+                        .run_if(not(in_state(GameState::Playing))),
+                    handle_buttons.after(confirm_menu_choice),
+                    update_button_colors.after(handle_buttons),
+                    toggle_pause,
+                    capture_rebinding.after(handle_buttons).after(toggle_pause),
+                ),
+                (apply_settings, save_settings, update_setting_values, update_binding_labels),
+                pause_on_focus_loss.run_if(in_state(GameState::Playing)),
+                // Runs in every state so slow motion cannot outlive the run that started it
+                end_slow_motion,
+                update_mode_label.run_if(in_state(GameState::Menu)),
                 (
-                    player_movement,
-                    spawn_coins,
-                    handle_coin_state_change,
-                    coin_movement,
-                    check_coin_collision,
-                    camera_follow,
-                    check_room_generation,
-                    despawn_invisible_coins,
-                    update_timer,
+                    // Player, world and run bookkeeping. Everything that moves reads the walls
+                    // from RoomNavigation, so they are brought up to date first
+                    update_room_navigation,
+                    (
+                        player_movement.after(update_room_navigation),
+                        check_coin_collision,
+                        collect_power_ups,
+                        collect_gold_coins,
+                        place_gold_coins,
+                        pulse_gold_coins,
+                        update_score_hud,
+                        update_rooms_hud,
+                        tick_power_up_effects,
+                        update_effect_hud,
+                        track_current_room,
+                        camera_follow,
+                        check_room_generation,
+                        update_timer,
+                    ),
+                    // Dash, hits and lives
+                    (
+                        start_dash,
+                        dash_movement.after(start_dash).after(update_room_navigation),
+                        tick_invulnerability,
+                        fade_dash_trail,
+                        update_dash_hud,
+                        handle_player_hits.after(check_coin_collision).after(check_chaser_collision),
+                        knockback_movement.after(update_room_navigation),
+                        flash_invulnerable,
+                        update_lives_hud,
+                    ),
+                    // Coin lifecycle and physics
+                    (
+                        spawn_coins,
+                        spawn_formations,
+                        update_emitters,
+                        handle_coin_state_change,
+                        update_coin_telegraphs,
+                        coin_homing,
+                        coin_orbiting,
+                        coin_movement.after(update_room_navigation),
+                        collide_coins.after(coin_movement),
+                        confine_coins_to_rooms.after(collide_coins),
+                        split_coins.after(limit_coin_bounces),
+                        limit_coin_bounces.after(coin_movement),
+                        speed_up_on_bounce.after(coin_movement),
+                        tick_coin_lifetimes,
+                        fade_out_coins,
+                        update_coin_simulation_region,
+                    ),
+                    // Chasers
+                    (
+                        spawn_chasers,
+                        chaser_movement.after(update_room_navigation),
+                        check_chaser_collision,
+                    ),
+                    // Near misses and combo
+                    (
+                        detect_near_misses.after(check_coin_collision),
+                        decay_combo,
+                        near_miss_feedback.after(detect_near_misses),
+                        animate_floating_text,
+                        update_combo_hud,
+                    ),
+                    // Map
+                    (
+                        toggle_minimap,
+                        draw_minimap.after(track_current_room).after(toggle_minimap),
+                    ),
+                    // Assists and debug switches
+                    (
+                        toggle_trajectory_preview,
+                        toggle_near_miss_slow_motion,
+                        toggle_coin_collisions,
+                        draw_trajectory_preview.after(update_room_navigation),
+                    ),
                 ).run_if(in_state(GameState::Playing)),
             ),
         )
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+// Maps room grid cells onto a map area in UI pixels, with y pointing down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapView {
+    // Point in cell units shown at the middle of the map
+    pub centre: Vec2,
+    pub cell_size: f32,
+    pub size: Vec2,
+}
+
+impl MapView {
+    // Centred on one cell at a fixed scale, as the corner minimap follows the player
+    pub fn around(cell: IVec2, cell_size: f32, size: Vec2) -> Self {
+        Self {
+            centre: cell.as_vec2(),
+            cell_size,
+            size,
+        }
+    }
+
+    // Scaled so every cell fits inside `size`, with cells no larger than `max_cell_size`
+    pub fn fit(cells: impl IntoIterator<Item = IVec2>, size: Vec2, max_cell_size: f32) -> Self {
+        let mut bounds: Option<(IVec2, IVec2)> = None;
+        for cell in cells {
+            bounds = Some(match bounds {
+                Some((min, max)) => (min.min(cell), max.max(cell)),
+                None => (cell, cell),
+            });
+        }
+
+        let Some((min, max)) = bounds else {
+            return Self::around(IVec2::ZERO, max_cell_size, size);
+        };
+        let span = (max - min + IVec2::ONE).as_vec2();
+        Self {
+            centre: (min + max).as_vec2() / 2.0,
+            cell_size: (size / span).min_element().min(max_cell_size),
+            size,
+        }
+    }
+
+    // Box `extent` cells across centred on `position`, both in cell units
+    pub fn rect(&self, position: Vec2, extent: Vec2) -> Rect {
+        let offset = (position - self.centre) * self.cell_size;
+        let centre = self.size / 2.0 + Vec2::new(offset.x, -offset.y);
+        Rect::from_center_size(centre, extent * self.cell_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn north_is_drawn_above() {
+        let view = MapView::around(IVec2::ZERO, 20.0, Vec2::splat(100.0));
+        let north = view.rect(Vec2::new(0.0, 1.0), Vec2::ONE);
+        assert_eq!(north.center(), Vec2::new(50.0, 30.0));
+        assert_eq!(north.size(), Vec2::splat(20.0));
+    }
+
+    #[test]
+    fn fit_keeps_every_cell_on_the_map() {
+        let cells = [IVec2::new(-3, 0), IVec2::new(4, 2), IVec2::new(0, -5)];
+        let view = MapView::fit(cells, Vec2::new(400.0, 300.0), 50.0);
+        for cell in cells {
+            let rect = view.rect(cell.as_vec2(), Vec2::ONE);
+            assert!(rect.min.x >= -1e-3 && rect.min.y >= -1e-3);
+            assert!(rect.max.x <= 400.0 + 1e-3 && rect.max.y <= 300.0 + 1e-3);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use bevy::{prelude::*, utils::HashMap};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::components::Direction;
+
+// Rooms as grid cells joined by passages, for finding routes between rooms
+#[derive(Default)]
+pub struct RoomGraph {
+    exits: HashMap<IVec2, Vec<Direction>>,
+}
+
+impl RoomGraph {
+    pub fn new<'a>(rooms: impl IntoIterator<Item = (IVec2, &'a [Direction])>) -> Self {
+        let mut exits = HashMap::default();
+        for (cell, room_exits) in rooms {
+            exits.entry(cell).or_insert_with(|| room_exits.to_vec());
+        }
+        Self { exits }
+    }
+
+    // Cells reachable from `cell` in one step; a passage needs an opening on both sides
+    pub fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        self.exits
+            .get(&cell)
+            .into_iter()
+            .flatten()
+            .filter_map(move |&exit| {
+                let next = cell + exit.to_vec2().as_ivec2();
+                let open = self.exits.get(&next)?.contains(&exit.opposite());
+                open.then_some(next)
+            })
+    }
+
+    // A* search from `start` to `goal`. The path excludes `start` and ends with `goal`.
+    pub fn find_path(&self, start: IVec2, goal: IVec2) -> Option<Vec<IVec2>> {
+        let heuristic = |cell: IVec2| ((goal - cell).abs().x + (goal - cell).abs().y) as u32;
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<IVec2, IVec2> = HashMap::default();
+        let mut cost: HashMap<IVec2, u32> = HashMap::default();
+        open.push(Reverse((heuristic(start), start.x, start.y)));
+        cost.insert(start, 0);
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let cell = IVec2::new(x, y);
+            if cell == goal {
+                let mut path = Vec::new();
+                let mut current = cell;
+                while current != start {
+                    path.push(current);
+                    current = came_from[&current];
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let next_cost = cost[&cell] + 1;
+            for next in self.neighbours(cell) {
+                if cost.get(&next).is_none_or(|&known| next_cost < known) {
+                    cost.insert(next, next_cost);
+                    came_from.insert(next, cell);
+                    open.push(Reverse((next_cost + heuristic(next), next.x, next.y)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Direction::*;
+
+    #[test]
+    fn path_follows_passages() {
+        // A U-shaped corridor: the direct route from (0, 0) to (1, 0) is walled off
+        let rooms = [
+            (IVec2::new(0, 0), vec![North]),
+            (IVec2::new(0, 1), vec![South, East]),
+            (IVec2::new(1, 1), vec![West, South]),
+            (IVec2::new(1, 0), vec![North, West]),
+        ];
+        let graph = RoomGraph::new(rooms.iter().map(|(cell, exits)| (*cell, exits.as_slice())));
+
+        let path = graph.find_path(IVec2::new(0, 0), IVec2::new(1, 0)).unwrap();
+        assert_eq!(path, vec![IVec2::new(0, 1), IVec2::new(1, 1), IVec2::new(1, 0)]);
+        assert_eq!(graph.find_path(IVec2::new(0, 0), IVec2::new(0, 0)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn one_sided_openings_are_not_passages() {
+        let rooms = [
+            (IVec2::new(0, 0), vec![East]),
+            (IVec2::new(1, 0), vec![North]),
+        ];
+        let graph = RoomGraph::new(rooms.iter().map(|(cell, exits)| (*cell, exits.as_slice())));
+
+        assert_eq!(graph.find_path(IVec2::new(0, 0), IVec2::new(1, 0)), None);
+    }
+}
@@ -0,0 +1,334 @@
+use bevy::{
+    prelude::*,
+    sprite::collide_aabb::{collide, Collision},
+    utils::HashMap,
+};
+
+use crate::constants::{ROOM_SIZE, WALL_GRID_CELL_SIZE};
+
+// Uniform grid bucketing items by the cells their bounding box overlaps
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(Vec::clear);
+    }
+
+    pub fn insert(&mut self, index: usize, position: Vec2, size: Vec2) {
+        for cell in self.cells_overlapping(position, size) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+
+    // Items sharing a cell with the box; an item spanning several cells may be returned more than once
+    pub fn query(&self, position: Vec2, size: Vec2) -> impl Iterator<Item = usize> + '_ {
+        self.cells_overlapping(position, size)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    fn cells_overlapping(&self, position: Vec2, size: Vec2) -> impl Iterator<Item = IVec2> {
+        let min = ((position - size / 2.0) / self.cell_size).floor().as_ivec2();
+        let max = ((position + size / 2.0) / self.cell_size).floor().as_ivec2();
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+}
+
+// Wall boxes with a spatial index so coins only test the walls around them
+pub struct WallIndex {
+    walls: Vec<(Vec3, Vec2)>,
+    grid: SpatialGrid,
+}
+
+impl WallIndex {
+    pub fn new(walls: Vec<(Vec3, Vec2)>) -> Self {
+        let mut grid = SpatialGrid::new(WALL_GRID_CELL_SIZE);
+        for (index, (position, size)) in walls.iter().enumerate() {
+            grid.insert(index, position.truncate(), *size);
+        }
+        Self { walls, grid }
+    }
+
+    pub fn near(&self, position: Vec3, size: Vec2) -> impl Iterator<Item = &(Vec3, Vec2)> {
+        self.grid.query(position.truncate(), size).map(|index| &self.walls[index])
+    }
+}
+
+impl Default for WallIndex {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+// Moves a box by `delta`, trying each axis separately so it slides along walls it runs into.
+// Returns the new position and which axes were blocked.
+pub fn slide_against_walls(position: Vec3, delta: Vec2, size: Vec2, walls: &WallIndex) -> (Vec3, BVec2) {
+    let blocked = |candidate: Vec3| {
+        walls
+            .near(candidate, size)
+            .any(|(wall_pos, wall_size)| collide(candidate, size, *wall_pos, *wall_size).is_some())
+    };
+
+    let mut new_pos = position;
+    let mut blocked_axes = BVec2::FALSE;
+    if delta.x != 0.0 {
+        let x_pos = new_pos + Vec3::new(delta.x, 0.0, 0.0);
+        if blocked(x_pos) {
+            blocked_axes.x = true;
+        } else {
+            new_pos = x_pos;
+        }
+    }
+    if delta.y != 0.0 {
+        let y_pos = new_pos + Vec3::new(0.0, delta.y, 0.0);
+        if blocked(y_pos) {
+            blocked_axes.y = true;
+        } else {
+            new_pos = y_pos;
+        }
+    }
+
+    (new_pos, blocked_axes)
+}
+
+// Distance between the edges of two boxes along the axis where they are furthest apart;
+// negative when they overlap
+pub fn box_gap(a_position: Vec2, a_size: Vec2, b_position: Vec2, b_size: Vec2) -> f32 {
+    let gap = (a_position - b_position).abs() - (a_size + b_size) / 2.0;
+    gap.max_element()
+}
+
+// Result of advancing a coin by one step
+pub struct CoinStep {
+    pub position: Vec3,
+    pub velocity: Vec2,
+    pub bounced: bool,
+}
+
+// Advances a coin by `delta` seconds, reflecting its velocity off the first wall it hits.
+// Shared by `coin_movement` and the trajectory preview so both agree on where coins go.
+pub fn step_coin(
+    position: Vec3,
+    velocity: Vec2,
+    size: Vec2,
+    walls: &WallIndex,
+    delta: f32,
+) -> CoinStep {
+    let new_pos = position + (velocity * delta).extend(0.0);
+    let mut step = CoinStep {
+        position: new_pos,
+        velocity,
+        bounced: false,
+    };
+
+    for (wall_pos, wall_size) in walls.near(new_pos, size) {
+        if let Some(collision) = collide(new_pos, size, *wall_pos, *wall_size) {
+            match collision {
+                Collision::Left | Collision::Right => step.velocity.x *= -1.0,
+                Collision::Top | Collision::Bottom => step.velocity.y *= -1.0,
+                Collision::Inside => step.position = position,
+            }
+            step.bounced = collision != Collision::Inside;
+            break;
+        }
+    }
+
+    step
+}
+
+// Puts a coin that left room `from` for `into` back against the edge it crossed and reflects
+// its velocity, for passages that act as walls. Shared by `confine_coins_to_rooms` and the
+// trajectory preview.
+pub fn bounce_off_room_edge(position: Vec3, velocity: Vec2, size: Vec2, from: IVec2, into: IVec2) -> (Vec3, Vec2) {
+    let room_centre = from.as_vec2() * ROOM_SIZE;
+    let inner_half = ROOM_SIZE / 2.0 - size / 2.0;
+    let crossed = into - from;
+    let (mut position, mut velocity) = (position, velocity);
+    if crossed.x != 0 {
+        velocity.x *= -1.0;
+        position.x = room_centre.x + inner_half.x * crossed.x.signum() as f32;
+    }
+    if crossed.y != 0 {
+        velocity.y *= -1.0;
+        position.y = room_centre.y + inner_half.y * crossed.y.signum() as f32;
+    }
+    (position, velocity)
+}
+
+// A moving coin as seen by the coin-to-coin collision pass
+pub struct CoinBody {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+    pub mass: f32,
+}
+
+// Resolves overlapping coins with elastic collisions weighted by mass, using `grid`
+// to find neighbours. Returns the number of collisions resolved.
+pub fn resolve_coin_collisions(bodies: &mut [CoinBody], grid: &mut SpatialGrid) -> usize {
+    grid.clear();
+    for (index, body) in bodies.iter().enumerate() {
+        grid.insert(index, body.position, Vec2::splat(body.radius * 2.0));
+    }
+
+    let mut collisions = 0;
+    for a in 0..bodies.len() {
+        let (position, size) = (bodies[a].position, Vec2::splat(bodies[a].radius * 2.0));
+        let mut neighbours: Vec<usize> = grid.query(position, size).filter(|&b| b > a).collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+
+        for b in neighbours {
+            if collide_bodies(bodies, a, b) {
+                collisions += 1;
+            }
+        }
+    }
+
+    collisions
+}
+
+fn collide_bodies(bodies: &mut [CoinBody], a: usize, b: usize) -> bool {
+    let offset = bodies[b].position - bodies[a].position;
+    let min_distance = bodies[a].radius + bodies[b].radius;
+    let distance = offset.length();
+    if distance >= min_distance || distance == 0.0 {
+        return false;
+    }
+
+    let normal = offset / distance;
+    let (mass_a, mass_b) = (bodies[a].mass, bodies[b].mass);
+    let total_mass = mass_a + mass_b;
+    // coins.ron requires positive masses, but a zero here would turn both coins into NaN
+    if total_mass <= 0.0 {
+        return false;
+    }
+
+    // Push the coins apart, the lighter one moving further
+    let overlap = min_distance - distance;
+    bodies[a].position -= normal * overlap * mass_b / total_mass;
+    bodies[b].position += normal * overlap * mass_a / total_mass;
+
+    // Only exchange momentum if the coins are moving toward each other
+    let approach = (bodies[a].velocity - bodies[b].velocity).dot(normal);
+    if approach > 0.0 {
+        let impulse = 2.0 * approach / total_mass;
+        bodies[a].velocity -= normal * impulse * mass_b;
+        bodies[b].velocity += normal * impulse * mass_a;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{prelude::*, rngs::StdRng};
+    use std::time::Instant;
+
+    fn body(position: Vec2, velocity: Vec2, mass: f32) -> CoinBody {
+        CoinBody {
+            position,
+            velocity,
+            radius: 12.0,
+            mass,
+        }
+    }
+
+    #[test]
+    fn equal_masses_swap_velocities() {
+        let mut bodies = vec![
+            body(Vec2::new(-10.0, 0.0), Vec2::new(100.0, 0.0), 1.0),
+            body(Vec2::new(10.0, 0.0), Vec2::new(-50.0, 0.0), 1.0),
+        ];
+
+        assert_eq!(resolve_coin_collisions(&mut bodies, &mut SpatialGrid::new(64.0)), 1);
+        assert!((bodies[0].velocity - Vec2::new(-50.0, 0.0)).length() < 1e-3);
+        assert!((bodies[1].velocity - Vec2::new(100.0, 0.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn collisions_conserve_momentum() {
+        let mut bodies = vec![
+            body(Vec2::new(0.0, 0.0), Vec2::new(120.0, 30.0), 4.0),
+            body(Vec2::new(15.0, 8.0), Vec2::new(-200.0, 10.0), 1.0),
+        ];
+        let momentum = |bodies: &[CoinBody]| bodies.iter().map(|b| b.velocity * b.mass).sum::<Vec2>();
+        let before = momentum(&bodies);
+
+        resolve_coin_collisions(&mut bodies, &mut SpatialGrid::new(64.0));
+
+        assert!((momentum(&bodies) - before).length() < 1e-2);
+        assert!(bodies[0].position.distance(bodies[1].position) >= 24.0 - 1e-3);
+    }
+
+    #[test]
+    fn box_gap_measures_edge_distance() {
+        let size = Vec2::splat(20.0);
+        assert_eq!(box_gap(Vec2::ZERO, size, Vec2::new(30.0, 5.0), size), 10.0);
+        assert!(box_gap(Vec2::ZERO, size, Vec2::new(10.0, 10.0), size) < 0.0);
+    }
+
+    #[test]
+    fn coins_bounce_back_off_the_edge_they_crossed() {
+        let size = Vec2::splat(20.0);
+        let outside = Vec3::new(ROOM_SIZE / 2.0 + 5.0, 10.0, 1.0);
+        let (position, velocity) = bounce_off_room_edge(outside, Vec2::new(100.0, 50.0), size, IVec2::ZERO, IVec2::X);
+
+        assert_eq!(position, Vec3::new(ROOM_SIZE / 2.0 - 10.0, 10.0, 1.0));
+        assert_eq!(velocity, Vec2::new(-100.0, 50.0));
+    }
+
+    #[test]
+    fn separated_coins_do_not_collide() {
+        let mut bodies = vec![
+            body(Vec2::new(0.0, 0.0), Vec2::new(100.0, 0.0), 1.0),
+            body(Vec2::new(100.0, 0.0), Vec2::new(-100.0, 0.0), 1.0),
+        ];
+
+        assert_eq!(resolve_coin_collisions(&mut bodies, &mut SpatialGrid::new(64.0)), 0);
+        assert_eq!(bodies[0].velocity, Vec2::new(100.0, 0.0));
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_coin_collisions() {
+        for count in [1_000, 2_000, 5_000] {
+            let mut rng = StdRng::seed_from_u64(count as u64);
+            let area = (count as f32).sqrt() * 40.0;
+            let mut bodies: Vec<CoinBody> = (0..count)
+                .map(|_| body(
+                    Vec2::new(rng.gen_range(0.0..area), rng.gen_range(0.0..area)),
+                    Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)) * 200.0,
+                    rng.gen_range(0.5..3.0),
+                ))
+                .collect();
+            let mut grid = SpatialGrid::new(64.0);
+
+            let frames = 200;
+            let start = Instant::now();
+            let mut collisions = 0;
+            for _ in 0..frames {
+                for body in bodies.iter_mut() {
+                    body.position += body.velocity / 60.0;
+                }
+                collisions += resolve_coin_collisions(&mut bodies, &mut grid);
+            }
+            let per_frame = start.elapsed() / frames;
+
+            println!("{count} coins: {per_frame:?} per frame, {} collisions per frame", collisions / frames as usize);
+        }
+    }
+}
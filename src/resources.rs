@@ -1,21 +1,542 @@
 use bevy::prelude::*;
 
-#[derive(Resource)]
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{components::GameState, input::Action, pathfinding::RoomGraph, physics::WallIndex};
+
+use crate::constants::{
+    CHASER_SPAWN_INTERVAL, CLASSIC_LIVES, COIN_STATIONARY_TIME, COMBO_DECAY_SECS, COMBO_MAX, DIFFICULTY_STEP_SECS,
+    FORMATION_INTERVAL_SECS, LEADERBOARD_SIZE, PLAYER_ACCELERATION, PLAYER_DECELERATION, PLAYER_SPEED,
+    SCORE_PER_SECOND, SIMULATION_ACTIVE_RING, SIMULATION_SLEEP_RING, SPAWN_ACTIVE_RING, SPAWN_ENTRY_GRACE_SECS,
+    SPAWN_MIN_COIN_SPACING, SPAWN_MIN_PLAYER_DISTANCE, WEIGHTY_LIVES,
+};
+
+#[derive(Resource, Default)]
 pub struct GameAssets {
     pub player_sprite: Handle<Image>,
     pub coin_sprite: Handle<Image>,
 }
 
-impl Default for GameAssets {
+#[derive(Resource, Default)]
+pub struct GameTimer {
+    pub elapsed_secs: f32,
+}
+
+impl GameTimer {
+    // Difficulty rises by one level every DIFFICULTY_STEP_SECS of survival
+    pub fn difficulty(&self) -> u32 {
+        (self.elapsed_secs / DIFFICULTY_STEP_SECS) as u32
+    }
+} 
+
+// Points earned from pickups and exploration; survival time is added on top
+#[derive(Resource, Default)]
+pub struct Score {
+    pub points: u32,
+}
+
+impl Score {
+    pub fn total(&self, elapsed_secs: f32) -> u32 {
+        self.points + (elapsed_secs * SCORE_PER_SECOND) as u32
+    }
+}
+
+// Exploration statistics for the current run
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub rooms_explored: u32,
+    // Furthest room reached, in steps from the starting room
+    pub max_distance: u32,
+}
+
+impl RunStats {
+    pub fn visit_room(&mut self, cell: IVec2) {
+        self.rooms_explored += 1;
+        self.max_distance = self.max_distance.max((cell.x.abs() + cell.y.abs()) as u32);
+    }
+}
+
+// What the map shows; the full-screen view takes over from the corner minimap while open
+#[derive(Resource)]
+pub struct MinimapSettings {
+    pub visible: bool,
+    pub full_screen: bool,
+    pub show_power_ups: bool,
+}
+
+impl Default for MinimapSettings {
     fn default() -> Self {
         Self {
-            player_sprite: Handle::default(),
-            coin_sprite: Handle::default(),
+            visible: true,
+            full_screen: false,
+            show_power_ups: true,
         }
     }
 }
 
+// Multiplier on near-miss points, built by chaining near misses and lost over time
+#[derive(Resource)]
+pub struct Combo {
+    pub multiplier: u32,
+    pub decay_timer: Timer,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Self {
+            multiplier: 1,
+            decay_timer: Timer::from_seconds(COMBO_DECAY_SECS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Combo {
+    // Scores a near miss at the current multiplier, then raises it
+    pub fn near_miss(&mut self, points: u32) -> u32 {
+        let awarded = points * self.multiplier;
+        self.multiplier = (self.multiplier + 1).min(COMBO_MAX);
+        self.decay_timer.reset();
+        awarded
+    }
+
+    pub fn decay(&mut self, delta: Duration) {
+        if self.multiplier > 1 {
+            let steps = self.decay_timer.tick(delta).times_finished_this_tick();
+            self.multiplier = self.multiplier.saturating_sub(steps).max(1);
+        }
+    }
+}
+
+// Real-time countdown for the brief slow motion after a near miss
 #[derive(Resource, Default)]
-pub struct GameTimer {
-    pub elapsed_secs: f32,
-} 
\ No newline at end of file
+pub struct SlowMotion {
+    pub timer: Option<Timer>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LeaderboardEntry {
+    pub score: u32,
+    pub survived_secs: f32,
+}
+
+// Best runs of the session, highest score first
+#[derive(Resource, Default)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    // Records a finished run, returning its rank if it made the board
+    pub fn submit(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        // Ties go to the earlier run
+        let rank = self.entries.iter().position(|existing| entry.score > existing.score)
+            .unwrap_or(self.entries.len());
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+}
+
+// Constraints on where `spawn_coins` may place new coins
+#[derive(Resource)]
+pub struct SpawnRules {
+    pub min_player_distance: f32,
+    pub min_coin_spacing: f32,
+    pub entry_grace_secs: f32,
+    // Coins spawn in the player's room and this many rooms around it
+    pub active_ring: i32,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self {
+            min_player_distance: SPAWN_MIN_PLAYER_DISTANCE,
+            min_coin_spacing: SPAWN_MIN_COIN_SPACING,
+            entry_grace_secs: SPAWN_ENTRY_GRACE_SECS,
+            active_ring: SPAWN_ACTIVE_RING,
+        }
+    }
+}
+
+// Rooms around the player, counted in rings, where coins are simulated or kept asleep
+#[derive(Resource)]
+pub struct SimulationRegion {
+    pub active_ring: i32,
+    pub sleep_ring: i32,
+}
+
+impl Default for SimulationRegion {
+    fn default() -> Self {
+        Self {
+            active_ring: SIMULATION_ACTIVE_RING,
+            sleep_ring: SIMULATION_SLEEP_RING,
+        }
+    }
+}
+
+// Schedules pattern spawns on top of the random coins from `spawn_coins`
+#[derive(Resource)]
+pub struct SpawnDirector {
+    pub formation_timer: Timer,
+    pub chaser_timer: Timer,
+}
+
+impl Default for SpawnDirector {
+    fn default() -> Self {
+        Self {
+            formation_timer: Timer::from_seconds(FORMATION_INTERVAL_SECS, TimerMode::Repeating),
+            chaser_timer: Timer::from_seconds(CHASER_SPAWN_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+// Switches for optional coin physics
+#[derive(Resource, Default)]
+pub struct CoinPhysics {
+    pub coin_collisions: bool,
+}
+
+// How fast coins run relative to the game clock, lowered by the slow-time and freeze power-ups
+#[derive(Resource)]
+pub struct CoinTimeScale {
+    pub scale: f32,
+}
+
+impl Default for CoinTimeScale {
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+// The room the player is in and when they entered it, in GameTimer seconds
+#[derive(Resource, Default)]
+pub struct CurrentRoom {
+    pub position: Option<Vec2>,
+    pub entered_at: f32,
+}
+
+// How the player's velocity responds to input
+#[derive(Clone, Copy)]
+pub enum MovementModel {
+    // Full speed the moment a key is pressed, stopping dead on release
+    Instant { speed: f32 },
+    // Speeds up and slows down gradually, in pixels per second squared
+    Momentum { acceleration: f32, deceleration: f32, max_speed: f32 },
+}
+
+// Rule set chosen from the main menu
+#[derive(Resource, Clone, Copy, PartialEq, Default)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    Weighty,
+}
+
+impl GameMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Weighty => "Weighty",
+        }
+    }
+
+    pub fn next(self) -> GameMode {
+        match self {
+            GameMode::Classic => GameMode::Weighty,
+            GameMode::Weighty => GameMode::Classic,
+        }
+    }
+
+    pub fn lives(self) -> u32 {
+        match self {
+            GameMode::Classic => CLASSIC_LIVES,
+            GameMode::Weighty => WEIGHTY_LIVES,
+        }
+    }
+
+    pub fn movement(self) -> MovementModel {
+        match self {
+            GameMode::Classic => MovementModel::Instant { speed: PLAYER_SPEED },
+            GameMode::Weighty => MovementModel::Momentum {
+                acceleration: PLAYER_ACCELERATION,
+                deceleration: PLAYER_DECELERATION,
+                max_speed: PLAYER_SPEED,
+            },
+        }
+    }
+}
+
+// Optional aids that help players read the game, saved as part of `UserSettings`
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssistSettings {
+    pub trajectory_preview: bool,
+    // Briefly slow the game down after every near miss
+    pub near_miss_slow_motion: bool,
+    // Dim the player while invulnerable instead of blinking
+    pub reduce_flashing: bool,
+}
+
+// State to go back to when leaving the settings screen
+#[derive(Resource, Default)]
+pub struct SettingsReturn {
+    pub state: GameState,
+}
+
+// Button highlighted for keyboard and gamepad navigation. Nothing is focused until a direction
+// is pressed, so mouse players never see a highlight.
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    pub button: Option<Entity>,
+}
+
+// Action waiting for a new binding on the settings screen. `armed` stays false for the
+// frame the capture starts on, so the press that started it is not taken as the binding.
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub action: Option<Action>,
+    pub armed: bool,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct SplitRule {
+    pub fragments: u32,
+    pub generations: u32,
+}
+
+fn default_stationary_time() -> f32 {
+    COIN_STATIONARY_TIME
+}
+
+fn default_mass() -> f32 {
+    1.0
+}
+
+// One entry of `assets/coins.ron`; behaviour components are attached for each rule that is set
+#[derive(Clone, Deserialize)]
+pub struct CoinArchetype {
+    pub name: String,
+    pub speed: f32,
+    pub size: f32,
+    pub colour: (f32, f32, f32),
+    pub spawn_weight: f32,
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    #[serde(default = "default_stationary_time")]
+    pub stationary_time: f32,
+    #[serde(default)]
+    pub min_difficulty: u32,
+    #[serde(default)]
+    pub room_bound: bool,
+    #[serde(default)]
+    pub max_bounces: Option<u32>,
+    #[serde(default)]
+    pub lifetime: Option<f32>,
+    #[serde(default)]
+    pub speedup_per_bounce: Option<f32>,
+    #[serde(default)]
+    pub steering: Option<f32>,
+    #[serde(default)]
+    pub split: Option<SplitRule>,
+    #[serde(default)]
+    pub orbit: Option<f32>,
+}
+
+impl CoinArchetype {
+    pub fn tint(&self) -> Color {
+        Color::rgb(self.colour.0, self.colour.1, self.colour.2)
+    }
+
+    // Rejects values the systems cannot use, such as a negative stationary time that would
+    // panic when turned into a Duration
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = |field: &str, value: f32| {
+            if value.is_finite() && value > 0.0 {
+                Ok(())
+            } else {
+                Err(format!("coin '{}': {field} must be greater than zero, got {value}", self.name))
+            }
+        };
+        let not_negative = |field: &str, value: f32| {
+            if value.is_finite() && value >= 0.0 {
+                Ok(())
+            } else {
+                Err(format!("coin '{}': {field} must not be negative, got {value}", self.name))
+            }
+        };
+
+        not_negative("speed", self.speed)?;
+        positive("size", self.size)?;
+        positive("spawn_weight", self.spawn_weight)?;
+        // Coin-to-coin collisions divide by the combined mass
+        positive("mass", self.mass)?;
+        not_negative("stationary_time", self.stationary_time)?;
+        // Zero bounces would never use up the limit, so the coin would never fade
+        if self.max_bounces == Some(0) {
+            return Err(format!("coin '{}': max_bounces must be at least 1", self.name));
+        }
+        if let Some(lifetime) = self.lifetime {
+            positive("lifetime", lifetime)?;
+        }
+        // A factor of zero would stop the coin dead on its first bounce
+        if let Some(speedup) = self.speedup_per_bounce {
+            positive("speedup_per_bounce", speedup)?;
+        }
+        if let Some(steering) = self.steering {
+            not_negative("steering", steering)?;
+        }
+        if let Some(orbit) = self.orbit {
+            if !orbit.is_finite() {
+                return Err(format!("coin '{}': orbit must be a number, got {orbit}", self.name));
+            }
+        }
+        if let Some(split) = self.split {
+            if split.fragments < 1 || split.generations < 1 {
+                return Err(format!(
+                    "coin '{}': split needs at least one fragment and one generation, got {} and {}",
+                    self.name, split.fragments, split.generations,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Tuning for chaser enemies from `assets/chasers.ron`, filled by `load_chaser_config`
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub struct ChaserConfig {
+    pub speed: f32,
+    // How quickly a chaser turns toward where it wants to go, from 0 to 1
+    pub aggression: f32,
+}
+
+impl ChaserConfig {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        let config: Self = ron::from_str(source).map_err(|err| err.to_string())?;
+        if !(config.speed.is_finite() && config.speed >= 0.0) {
+            return Err(format!("speed must not be negative, got {}", config.speed));
+        }
+        if !(0.0..=1.0).contains(&config.aggression) {
+            return Err(format!("aggression must be from 0 to 1, got {}", config.aggression));
+        }
+        Ok(config)
+    }
+}
+
+// Room graph that chasers navigate by and the walls everything that moves collides with,
+// rebuilt only when rooms are added or removed
+#[derive(Resource, Default)]
+pub struct RoomNavigation {
+    pub graph: RoomGraph,
+    pub walls: WallIndex,
+}
+
+// Registry of coin kinds the spawner samples from, filled by `load_coin_archetypes`
+#[derive(Resource, Default)]
+pub struct CoinArchetypes {
+    pub archetypes: Vec<CoinArchetype>,
+}
+
+impl CoinArchetypes {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        let archetypes: Vec<CoinArchetype> = ron::from_str(source).map_err(|err| err.to_string())?;
+        if archetypes.is_empty() {
+            return Err("no coin kinds are defined".to_string());
+        }
+        for archetype in &archetypes {
+            archetype.validate()?;
+        }
+        Ok(Self { archetypes })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CoinArchetype> {
+        self.archetypes.iter().find(|archetype| archetype.name == name)
+    }
+
+    // Archetypes that may spawn at the given difficulty level
+    pub fn available(&self, difficulty: u32) -> impl Iterator<Item = &CoinArchetype> {
+        self.archetypes
+            .iter()
+            .filter(move |archetype| archetype.min_difficulty <= difficulty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u32) -> LeaderboardEntry {
+        LeaderboardEntry { score, survived_secs: 0.0 }
+    }
+
+    #[test]
+    fn built_in_coin_definitions_are_valid() {
+        assert!(CoinArchetypes::from_ron(crate::embedded_assets::COIN_DEFINITIONS).is_ok());
+    }
+
+    #[test]
+    fn out_of_range_coin_fields_are_rejected() {
+        let coin = |fields: &str| {
+            format!("[(name: \"test\", speed: 100.0, size: 20.0, colour: (1.0, 1.0, 1.0), {fields})]")
+        };
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0")).is_ok());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 0.0")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, stationary_time: -1.0")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, stationary_time: NaN")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, mass: 0.0")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, max_bounces: Some(0)")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, speedup_per_bounce: Some(0.0)")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, split: Some((fragments: 2, generations: 1))")).is_ok());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, split: Some((fragments: 0, generations: 1))")).is_err());
+        assert!(CoinArchetypes::from_ron(&coin("spawn_weight: 1.0, split: Some((fragments: 2, generations: 0))")).is_err());
+        assert!(CoinArchetypes::from_ron("[]").is_err());
+    }
+
+    #[test]
+    fn chaser_config_is_checked() {
+        assert!(ChaserConfig::from_ron(crate::embedded_assets::CHASER_DEFINITIONS).is_ok());
+        assert!(ChaserConfig::from_ron("(speed: 100.0, aggression: 1.5)").is_err());
+        assert!(ChaserConfig::from_ron("(speed: -1.0, aggression: 0.5)").is_err());
+    }
+
+    #[test]
+    fn visiting_rooms_tracks_count_and_furthest_distance() {
+        let mut stats = RunStats::default();
+        stats.visit_room(IVec2::ZERO);
+        stats.visit_room(IVec2::new(2, -1));
+        stats.visit_room(IVec2::new(1, 0));
+
+        assert_eq!(stats.rooms_explored, 3);
+        assert_eq!(stats.max_distance, 3);
+    }
+
+    #[test]
+    fn combo_builds_and_decays() {
+        let mut combo = Combo::default();
+        assert_eq!(combo.near_miss(10), 10);
+        assert_eq!(combo.near_miss(10), 20);
+        assert_eq!(combo.multiplier, 3);
+
+        combo.decay(Duration::from_secs_f32(COMBO_DECAY_SECS * 1.5));
+        assert_eq!(combo.multiplier, 2);
+        combo.decay(Duration::from_secs_f32(COMBO_DECAY_SECS * 10.0));
+        assert_eq!(combo.multiplier, 1);
+    }
+
+    #[test]
+    fn leaderboard_ranks_by_score_and_keeps_the_best() {
+        let mut leaderboard = Leaderboard::default();
+        for score in [300, 100, 500, 200, 400] {
+            leaderboard.submit(entry(score));
+        }
+
+        assert_eq!(leaderboard.submit(entry(50)), None);
+        assert_eq!(leaderboard.submit(entry(350)), Some(2));
+        let scores: Vec<u32> = leaderboard.entries.iter().map(|entry| entry.score).collect();
+        assert_eq!(scores, vec![500, 400, 350, 300, 200]);
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::{prelude::*, window::WindowMode};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::PathBuf};
+
+use crate::{
+    constants::{INPUT_PROFILE_COUNT, SETTINGS_DIR, SETTINGS_FILE, UI_SCALES},
+    input::InputSettings,
+    resources::AssistSettings,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowModeSetting {
+    pub fn window_mode(self) -> WindowMode {
+        match self {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    // Multiplier on how often coins spawn at random
+    pub fn spawn_rate(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+}
+
+// Player preferences, saved to the user's config directory whenever they change.
+// Missing fields fall back to their defaults so older files keep loading.
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    // Master volume from 0 to 1; kept for when the game has sound
+    pub volume: f32,
+    pub window_mode: WindowModeSetting,
+    pub vsync: bool,
+    pub ui_scale: f32,
+    pub difficulty: Difficulty,
+    pub assists: AssistSettings,
+    pub controls: InputSettings,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            volume: 0.8,
+            window_mode: WindowModeSetting::default(),
+            vsync: true,
+            ui_scale: 1.0,
+            difficulty: Difficulty::default(),
+            assists: AssistSettings::default(),
+            controls: InputSettings::default(),
+        }
+    }
+}
+
+// An entry on the settings screen
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingKind {
+    Volume,
+    WindowMode,
+    Vsync,
+    UiScale,
+    Difficulty,
+    TrajectoryPreview,
+    NearMissSlowMotion,
+    ReduceFlashing,
+    ControlProfile,
+}
+
+impl SettingKind {
+    pub const ALL: [SettingKind; 9] = [
+        SettingKind::Volume,
+        SettingKind::WindowMode,
+        SettingKind::Vsync,
+        SettingKind::UiScale,
+        SettingKind::Difficulty,
+        SettingKind::TrajectoryPreview,
+        SettingKind::NearMissSlowMotion,
+        SettingKind::ReduceFlashing,
+        SettingKind::ControlProfile,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SettingKind::Volume => "Volume",
+            SettingKind::WindowMode => "Window",
+            SettingKind::Vsync => "VSync",
+            SettingKind::UiScale => "UI scale",
+            SettingKind::Difficulty => "Difficulty",
+            SettingKind::TrajectoryPreview => "Trajectory preview",
+            SettingKind::NearMissSlowMotion => "Near-miss slow motion",
+            SettingKind::ReduceFlashing => "Reduce flashing",
+            SettingKind::ControlProfile => "Profile",
+        }
+    }
+}
+
+impl UserSettings {
+    // Steps a setting to its next value, wrapping around at the end
+    pub fn cycle(&mut self, kind: SettingKind) {
+        match kind {
+            SettingKind::Volume => {
+                // Work in whole tenths so repeated steps do not drift
+                let tenths = (self.volume * 10.0).round() as u32;
+                self.volume = ((tenths + 1) % 11) as f32 / 10.0;
+            }
+            SettingKind::WindowMode => {
+                self.window_mode = match self.window_mode {
+                    WindowModeSetting::Windowed => WindowModeSetting::Borderless,
+                    WindowModeSetting::Borderless => WindowModeSetting::Fullscreen,
+                    WindowModeSetting::Fullscreen => WindowModeSetting::Windowed,
+                };
+            }
+            SettingKind::Vsync => self.vsync = !self.vsync,
+            SettingKind::UiScale => {
+                let next = UI_SCALES.iter().position(|&scale| scale > self.ui_scale + 0.01).unwrap_or(0);
+                self.ui_scale = UI_SCALES[next];
+            }
+            SettingKind::Difficulty => {
+                self.difficulty = match self.difficulty {
+                    Difficulty::Easy => Difficulty::Normal,
+                    Difficulty::Normal => Difficulty::Hard,
+                    Difficulty::Hard => Difficulty::Easy,
+                };
+            }
+            SettingKind::TrajectoryPreview => {
+                self.assists.trajectory_preview = !self.assists.trajectory_preview;
+            }
+            SettingKind::NearMissSlowMotion => {
+                self.assists.near_miss_slow_motion = !self.assists.near_miss_slow_motion;
+            }
+            SettingKind::ReduceFlashing => {
+                self.assists.reduce_flashing = !self.assists.reduce_flashing;
+            }
+            SettingKind::ControlProfile => {
+                self.controls.active_profile = (self.controls.active_profile + 1) % INPUT_PROFILE_COUNT;
+            }
+        }
+    }
+
+    pub fn value_label(&self, kind: SettingKind) -> String {
+        let on_off = |value: bool| if value { "On" } else { "Off" }.to_string();
+        match kind {
+            SettingKind::Volume => format!("{}%", (self.volume * 100.0).round()),
+            SettingKind::WindowMode => format!("{:?}", self.window_mode),
+            SettingKind::Vsync => on_off(self.vsync),
+            SettingKind::UiScale => format!("{}%", (self.ui_scale * 100.0).round()),
+            SettingKind::Difficulty => format!("{:?}", self.difficulty),
+            SettingKind::TrajectoryPreview => on_off(self.assists.trajectory_preview),
+            SettingKind::NearMissSlowMotion => on_off(self.assists.near_miss_slow_motion),
+            SettingKind::ReduceFlashing => on_off(self.assists.reduce_flashing),
+            SettingKind::ControlProfile => format!("{}", self.controls.active_profile + 1),
+        }
+    }
+
+    // Reads the saved settings, falling back to the defaults if there are none or they are invalid
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+        let Ok(source) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        let mut settings: Self = ron::from_str(&source).unwrap_or_else(|err| {
+            error!("Failed to parse {}: {err}, using default settings", path.display());
+            Self::default()
+        });
+        settings.normalize();
+        settings
+    }
+
+    // Brings hand-edited values back into range: the UI scale snaps to the nearest one the
+    // settings screen offers and the volume is clamped to 0..=1
+    pub fn normalize(&mut self) {
+        let default = Self::default();
+        self.ui_scale = if self.ui_scale.is_nan() {
+            default.ui_scale
+        } else {
+            UI_SCALES
+                .into_iter()
+                .min_by(|a, b| (a - self.ui_scale).abs().total_cmp(&(b - self.ui_scale).abs()))
+                .unwrap_or(default.ui_scale)
+        };
+        self.volume = if self.volume.is_nan() { default.volume } else { self.volume.clamp(0.0, 1.0) };
+        self.controls.normalize();
+    }
+
+    pub fn save(&self) {
+        let Some(path) = config_path() else {
+            warn!("No config directory found, settings will not be saved");
+            return;
+        };
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+                }
+                fs::write(&path, contents).map_err(|err| err.to_string())
+            });
+        if let Err(err) = result {
+            error!("Failed to save settings to {}: {err}", path.display());
+        }
+    }
+}
+
+// Where the settings file lives: the platform's per-user config directory
+pub fn config_path() -> Option<PathBuf> {
+    let non_empty = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    let base = if cfg!(windows) {
+        non_empty("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        non_empty("XDG_CONFIG_HOME").or_else(|| non_empty("HOME").map(|home| home.join(".config")))
+    };
+    base.map(|dir| dir.join(SETTINGS_DIR).join(SETTINGS_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_ron() {
+        let mut settings = UserSettings::default();
+        settings.cycle(SettingKind::WindowMode);
+        settings.cycle(SettingKind::Difficulty);
+        settings.cycle(SettingKind::TrajectoryPreview);
+
+        let source = ron::to_string(&settings).unwrap();
+        assert_eq!(ron::from_str::<UserSettings>(&source).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: UserSettings = ron::from_str("(vsync: false)").unwrap();
+        assert!(!settings.vsync);
+        assert_eq!(settings.ui_scale, UserSettings::default().ui_scale);
+    }
+
+    #[test]
+    fn loaded_values_are_brought_into_range() {
+        let mut settings: UserSettings = ron::from_str("(volume: 3.0, ui_scale: 1.3)").unwrap();
+        settings.normalize();
+        assert_eq!(settings.volume, 1.0);
+        assert_eq!(settings.ui_scale, 1.25);
+
+        let mut settings: UserSettings = ron::from_str("(volume: -0.5, ui_scale: 40.0)").unwrap();
+        settings.normalize();
+        assert_eq!(settings.volume, 0.0);
+        assert_eq!(settings.ui_scale, 1.5);
+
+        let mut settings: UserSettings = ron::from_str("(volume: NaN, ui_scale: NaN)").unwrap();
+        settings.normalize();
+        assert_eq!(settings, UserSettings::default());
+    }
+
+    #[test]
+    fn volume_wraps_around() {
+        let mut settings = UserSettings { volume: 0.9, ..default() };
+        settings.cycle(SettingKind::Volume);
+        assert_eq!(settings.volume, 1.0);
+        settings.cycle(SettingKind::Volume);
+        assert_eq!(settings.volume, 0.0);
+    }
+}
@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    components::{Direction, Room},
+    constants::*,
+    resources::{CurrentRoom, SpawnRules},
+};
+
+// Grid cell of the room containing `position`; rooms sit on a ROOM_SIZE grid
+pub fn room_cell(position: Vec2) -> IVec2 {
+    (position / ROOM_SIZE).round().as_ivec2()
+}
+
+// Whether `position` lies in a room within `ring` rooms of the player's room
+pub fn in_active_ring(position: Vec2, player_pos: Vec2, ring: i32) -> bool {
+    let offset = room_cell(position) - room_cell(player_pos);
+    offset.x.abs().max(offset.y.abs()) <= ring
+}
+
+// Whether the player entered `room` too recently for coins to spawn in it
+pub fn in_entry_grace(room: &Room, current_room: &CurrentRoom, rules: &SpawnRules, now: f32) -> bool {
+    current_room.position == Some(room.position) && now - current_room.entered_at < rules.entry_grace_secs
+}
+
+// Picks a point in `room` that respects the spawn rules, or None if no attempt succeeded.
+// Points come from the room's spawn zones, or anywhere inside its walls if it has none.
+pub fn pick_spawn_position(
+    rng: &mut impl Rng,
+    room: &Room,
+    player_pos: Vec2,
+    occupied: &[Vec2],
+    rules: &SpawnRules,
+) -> Option<Vec2> {
+    let interior = Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(ROOM_SIZE / 2.0 - SPAWN_MARGIN));
+
+    for _ in 0..SPAWN_ATTEMPTS {
+        let zone = room.spawn_zones.choose(rng).copied().unwrap_or(interior);
+        let position = room.position + Vec2::new(
+            rng.gen_range(zone.min.x..=zone.max.x),
+            rng.gen_range(zone.min.y..=zone.max.y),
+        );
+
+        let clear_of_player = position.distance(player_pos) >= rules.min_player_distance;
+        let clear_of_coins = occupied
+            .iter()
+            .all(|other| position.distance(*other) >= rules.min_coin_spacing);
+
+        if clear_of_player && clear_of_coins {
+            return Some(position);
+        }
+    }
+
+    None
+}
+
+// Bullet-hell style patterns the spawn director places in a room
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Formation {
+    // Coins on a circle that burst outward together
+    Ring,
+    // Coins along one edge released one after another so the launch sweeps across it
+    Line,
+    // Coins released in sequence from the room centre in a turning direction
+    Spiral,
+    // Coins along one edge released together, leaving a gap to slip through
+    Wall,
+}
+
+impl Formation {
+    pub const ALL: [Formation; 4] = [Formation::Ring, Formation::Line, Formation::Spiral, Formation::Wall];
+}
+
+// One coin of a formation, relative to the room centre
+pub struct FormationSlot {
+    pub offset: Vec2,
+    pub direction: Vec2,
+    // Extra stationary time so formations can release coins in sequence
+    pub delay: f32,
+}
+
+pub fn formation_slots(formation: Formation, rng: &mut impl Rng) -> Vec<FormationSlot> {
+    let interior = ROOM_SIZE / 2.0 - SPAWN_MARGIN;
+    let edge_slots = (interior * 2.0 / FORMATION_SPACING) as usize;
+
+    // Line and wall formations start at a random edge and launch across the room
+    let launch = Direction::all().choose(rng).copied().unwrap_or(Direction::North).to_vec2();
+    let edge_slot = |index: usize| {
+        let along = -interior + FORMATION_SPACING * (index as f32 + 0.5);
+        -launch * interior + launch.perp() * along
+    };
+
+    match formation {
+        Formation::Ring => (0..FORMATION_RING_COINS)
+            .map(|index| {
+                let direction = Vec2::from_angle(std::f32::consts::TAU * index as f32 / FORMATION_RING_COINS as f32);
+                FormationSlot {
+                    offset: direction * FORMATION_RING_RADIUS,
+                    direction,
+                    delay: 0.0,
+                }
+            })
+            .collect(),
+        Formation::Line => (0..edge_slots)
+            .map(|index| FormationSlot {
+                offset: edge_slot(index),
+                direction: launch,
+                delay: index as f32 * FORMATION_STAGGER_SECS,
+            })
+            .collect(),
+        Formation::Spiral => {
+            let start = rng.gen_range(0.0..std::f32::consts::TAU);
+            (0..FORMATION_SPIRAL_COINS)
+                .map(|index| {
+                    let direction = Vec2::from_angle(start + index as f32 * FORMATION_SPIRAL_TURN);
+                    FormationSlot {
+                        offset: direction * (FORMATION_SPIRAL_RADIUS + index as f32 * FORMATION_SPIRAL_GROWTH),
+                        direction,
+                        delay: index as f32 * FORMATION_STAGGER_SECS,
+                    }
+                })
+                .collect()
+        }
+        Formation::Wall => {
+            let gap_start = rng.gen_range(0..=edge_slots - FORMATION_WALL_GAP);
+            (0..edge_slots)
+                .filter(|index| !(gap_start..gap_start + FORMATION_WALL_GAP).contains(index))
+                .map(|index| FormationSlot {
+                    offset: edge_slot(index),
+                    direction: launch,
+                    delay: 0.0,
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn room_at(position: Vec2, spawn_zones: Vec<Rect>) -> Room {
+        Room {
+            position,
+            exits: Vec::new(),
+            spawn_zones,
+            first_visited_at: None,
+        }
+    }
+
+    #[test]
+    fn spawn_positions_respect_rules() {
+        let rules = SpawnRules::default();
+        let room = room_at(Vec2::new(ROOM_SIZE, -ROOM_SIZE), Vec::new());
+        let half_interior = ROOM_SIZE / 2.0 - SPAWN_MARGIN;
+
+        for seed in 0..500 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let player_pos = room.position + Vec2::new(
+                rng.gen_range(-half_interior..half_interior),
+                rng.gen_range(-half_interior..half_interior),
+            );
+            let mut occupied = Vec::new();
+
+            for _ in 0..10 {
+                let Some(position) = pick_spawn_position(&mut rng, &room, player_pos, &occupied, &rules) else {
+                    continue;
+                };
+
+                let offset = position - room.position;
+                assert!(offset.x.abs() <= half_interior && offset.y.abs() <= half_interior, "seed {seed}: outside room");
+                assert!(position.distance(player_pos) >= rules.min_player_distance, "seed {seed}: too close to player");
+                for other in &occupied {
+                    assert!(position.distance(*other) >= rules.min_coin_spacing, "seed {seed}: too close to a coin");
+                }
+                occupied.push(position);
+            }
+        }
+    }
+
+    #[test]
+    fn spawn_positions_stay_in_zones() {
+        let rules = SpawnRules::default();
+        let zones = vec![
+            Rect::new(-200.0, -200.0, -100.0, -100.0),
+            Rect::new(100.0, 150.0, 200.0, 200.0),
+        ];
+        let room = room_at(Vec2::ZERO, zones.clone());
+
+        for seed in 0..500 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            if let Some(position) = pick_spawn_position(&mut rng, &room, Vec2::ZERO, &[], &rules) {
+                assert!(zones.iter().any(|zone| zone.contains(position)), "seed {seed}: outside spawn zones");
+            }
+        }
+    }
+
+    #[test]
+    fn formations_stay_inside_the_room() {
+        let interior = ROOM_SIZE / 2.0 - SPAWN_MARGIN;
+
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for formation in Formation::ALL {
+                let slots = formation_slots(formation, &mut rng);
+                assert!(!slots.is_empty());
+                for slot in slots {
+                    assert!(slot.offset.abs().max_element() <= interior, "{formation:?} slot outside the room");
+                    assert!((slot.direction.length() - 1.0).abs() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wall_formation_leaves_a_gap() {
+        let edge_slots = ((ROOM_SIZE / 2.0 - SPAWN_MARGIN) * 2.0 / FORMATION_SPACING) as usize;
+
+        for seed in 0..100 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let slots = formation_slots(Formation::Wall, &mut rng);
+            assert_eq!(slots.len(), edge_slots - FORMATION_WALL_GAP);
+        }
+    }
+
+    #[test]
+    fn active_ring_covers_neighbouring_rooms() {
+        let player_pos = Vec2::new(ROOM_SIZE * 2.0 + 250.0, -100.0);
+
+        assert!(in_active_ring(Vec2::new(ROOM_SIZE * 2.0, 0.0), player_pos, 0));
+        assert!(in_active_ring(Vec2::new(ROOM_SIZE * 3.0, ROOM_SIZE), player_pos, 1));
+        assert!(!in_active_ring(Vec2::new(ROOM_SIZE * 3.0, ROOM_SIZE), player_pos, 0));
+        assert!(!in_active_ring(Vec2::new(0.0, 0.0), player_pos, 1));
+    }
+
+    #[test]
+    fn entry_grace_blocks_only_the_entered_room() {
+        let rules = SpawnRules::default();
+        let current_room = CurrentRoom {
+            position: Some(Vec2::ZERO),
+            entered_at: 10.0,
+        };
+        let entered = room_at(Vec2::ZERO, Vec::new());
+        let neighbour = room_at(Vec2::new(ROOM_SIZE, 0.0), Vec::new());
+
+        assert!(in_entry_grace(&entered, &current_room, &rules, 10.5));
+        assert!(!in_entry_grace(&entered, &current_room, &rules, 10.0 + rules.entry_grace_secs));
+        assert!(!in_entry_grace(&neighbour, &current_room, &rules, 10.5));
+    }
+}
@@ -1,17 +1,27 @@
 use bevy::{
     prelude::*,
-    sprite::collide_aabb::{collide, Collision},
+    sprite::collide_aabb::collide,
     app::AppExit,
+    time::Real,
     render::texture::{CompressedImageFormats, ImageType, ImageSampler},
+    utils::HashMap,
+    window::{PresentMode, PrimaryWindow, WindowFocused},
 };
-use rand::prelude::*;
-use std::time::Duration;
+use rand::{distributions::WeightedIndex, prelude::*};
+use std::{collections::HashSet, time::Duration};
 
 use crate::{
-    components::{Player, Wall, MainCamera, Coin, Room, Direction, GameState, GameOverText, MenuUI, ButtonAction, Cleanup, MenuCleanup},
+    components::{Player, PlayerMotion, Health, Knockback, DashCooldown, Dashing, Invulnerable, DashTrail, PowerUp, PowerUpKind, Shield, SlowTime, Shrunk, Frozen, EffectIcon, GoldCoin, Grazing, GrazeChange, NearMissed, FloatingText, Wall, MainCamera, Coin, BounceLimit, BounceSpeedup, Lifetime, Fading, Homing, Splitting, Orbiting, TelegraphDot, Sleeping, CoinRoom, RoomBound, Room, RoomTemplate, Emitter, EmitterPattern, Chaser, Direction, GameState, GameOverText, MenuUI, PauseMenu, SettingsMenu, SettingValue, BindingLabel, ButtonAction, DefaultButton, ModeLabel, MinimapRoot, TimerText, LivesText, ScoreText, RoomsText, ComboText, LeaderboardText, DashText, Cleanup, MenuCleanup},
     constants::*,
+    events::{CoinBounced, NearMiss, PlayerHit},
+    input::{nearest_in_direction, Action, ActionMap, ActionState, Binding},
+    minimap::MapView,
+    pathfinding::RoomGraph,
+    physics::{bounce_off_room_edge, box_gap, resolve_coin_collisions, slide_against_walls, step_coin, CoinBody, SpatialGrid, WallIndex},
+    spawning::{formation_slots, in_active_ring, in_entry_grace, pick_spawn_position, room_cell, Formation},
     resources::*,
-    embedded_assets::{BALL_BYTES, COIN_BYTES},
+    settings::{SettingKind, UserSettings},
+    embedded_assets::{BALL_BYTES, CHASER_DEFINITIONS, COIN_BYTES, COIN_DEFINITIONS},
 };
 
 pub fn load_assets(
@@ -39,7 +49,46 @@ pub fn load_assets(
     game_assets.coin_sprite = image_assets.add(coin_image);
 }
 
-pub fn setup(mut commands: Commands, game_assets: Res<GameAssets>) {
+pub fn load_coin_archetypes(mut coin_archetypes: ResMut<CoinArchetypes>) {
+    // Prefer the definitions in the assets directory, found relative to the working directory,
+    // so they can be edited without rebuilding
+    let source = std::fs::read_to_string(COIN_DEFINITIONS_PATH)
+        .unwrap_or_else(|_| COIN_DEFINITIONS.to_string());
+
+    *coin_archetypes = match CoinArchetypes::from_ron(&source) {
+        Ok(archetypes) => archetypes,
+        Err(err) => {
+            error!("Invalid coin definitions in {COIN_DEFINITIONS_PATH}: {err}, using built-in coin definitions");
+            CoinArchetypes::from_ron(COIN_DEFINITIONS).expect("built-in coin definitions are valid")
+        }
+    };
+
+    let names: Vec<&str> = coin_archetypes.archetypes.iter().map(|archetype| archetype.name.as_str()).collect();
+    info!("Loaded {} coin kinds: {}", names.len(), names.join(", "));
+}
+
+pub fn load_chaser_config(mut chaser_config: ResMut<ChaserConfig>) {
+    let source = std::fs::read_to_string(CHASER_DEFINITIONS_PATH)
+        .unwrap_or_else(|_| CHASER_DEFINITIONS.to_string());
+
+    *chaser_config = match ChaserConfig::from_ron(&source) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Invalid chaser settings in {CHASER_DEFINITIONS_PATH}: {err}, using built-in settings");
+            ChaserConfig::from_ron(CHASER_DEFINITIONS).expect("built-in chaser settings are valid")
+        }
+    };
+}
+
+pub fn setup(mut commands: Commands, game_assets: Res<GameAssets>, game_mode: Res<GameMode>) {
+    commands.insert_resource(GameTimer::default());
+    commands.insert_resource(CurrentRoom::default());
+    commands.insert_resource(SpawnDirector::default());
+    commands.insert_resource(CoinTimeScale::default());
+    commands.insert_resource(Score::default());
+    commands.insert_resource(Combo::default());
+    commands.insert_resource(RunStats::default());
+
     // Camera
     commands.spawn((
         Camera2dBundle::default(),
@@ -59,14 +108,17 @@ pub fn setup(mut commands: Commands, game_assets: Res<GameAssets>) {
             ..default()
         },
         Player,
+        PlayerMotion::default(),
+        Health { lives: game_mode.lives() },
+        DashCooldown::ready(),
         Cleanup,
     ));
 
     // Initial room
-    spawn_room(&mut commands, Vec2::ZERO, Direction::all());
+    spawn_room(&mut commands, Vec2::ZERO, Direction::all(), RoomTemplate::Open);
 }
 
-pub fn spawn_room(commands: &mut Commands, position: Vec2, exits: Vec<Direction>) {
+pub fn spawn_room(commands: &mut Commands, position: Vec2, exits: Vec<Direction>, template: RoomTemplate) {
     let mut rng = rand::thread_rng();
     
     // Filter exits based on HOLE_CHANCE
@@ -79,12 +131,57 @@ pub fn spawn_room(commands: &mut Commands, position: Vec2, exits: Vec<Direction>
         Room {
             position,
             exits: actual_exits.clone(),
+            spawn_zones: template.spawn_zones(),
+            first_visited_at: None,
         },
         Cleanup,
-    ));
+    )).with_children(|parent| {
+        // Emitters belong to the room so they are despawned along with it
+        for (offset, emitter) in template.emitters() {
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: EMITTER_IDLE_COLOR,
+                        custom_size: Some(EMITTER_SIZE),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(offset.extend(0.5)),
+                    ..default()
+                },
+                emitter,
+            ));
+        }
+
+        // Power-ups go in the template's spawn zones so they stay clear of emitters
+        if let Some(kind) = PowerUpKind::roll(&mut rng) {
+            let inner = ROOM_SIZE / 2.0 - SPAWN_MARGIN;
+            let zone = template
+                .spawn_zones()
+                .choose(&mut rng)
+                .copied()
+                .unwrap_or(Rect::new(-inner, -inner, inner, inner));
+            let offset = Vec2::new(
+                rng.gen_range(zone.min.x..zone.max.x),
+                rng.gen_range(zone.min.y..zone.max.y),
+            );
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: kind.color(),
+                        custom_size: Some(POWERUP_SIZE),
+                        ..default()
+                    },
+                    // Turned on its corner so it reads differently from a coin
+                    transform: Transform::from_translation(offset.extend(0.5))
+                        .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+                    ..default()
+                },
+                PowerUp { kind },
+            ));
+        }
+    });
 
     let half_size = ROOM_SIZE / 2.0;
-    let half_passage = PASSAGE_WIDTH / 2.0;
 
     // Spawn walls based on exits
     let mut wall_segments = Vec::new();
@@ -188,475 +285,2763 @@ pub fn spawn_wall(commands: &mut Commands, position: Vec2, size: Vec2) {
 }
 
 pub fn player_movement(
-    mut player_query: Query<(&mut Transform, &Sprite), With<Player>>,
-    wall_query: Query<(&Transform, &Sprite), (With<Wall>, Without<Player>)>,
-    keyboard: Res<Input<KeyCode>>,
+    mut player_query: Query<
+        (&mut Transform, &mut PlayerMotion, &Sprite),
+        (With<Player>, Without<Dashing>, Without<Knockback>),
+    >,
+    navigation: Res<RoomNavigation>,
+    action_state: Res<ActionState>,
+    game_mode: Res<GameMode>,
     time: Res<Time>,
 ) {
-    if let Ok((mut transform, sprite)) = player_query.get_single_mut() {
-        let mut direction = Vec2::ZERO;
-
-        if keyboard.pressed(KeyCode::W) {
-            direction.y += 1.0;
+    if let Ok((mut transform, mut motion, sprite)) = player_query.get_single_mut() {
+        // Up to length 1; a stick pushed part of the way gives part speed
+        let input = action_state.movement();
+        let delta_seconds = time.delta_seconds();
+        if input != Vec2::ZERO {
+            motion.facing = input.normalize();
         }
-        if keyboard.pressed(KeyCode::S) {
-            direction.y -= 1.0;
+
+        motion.velocity = match game_mode.movement() {
+            MovementModel::Instant { speed } => input * speed,
+            MovementModel::Momentum { acceleration, deceleration, max_speed } => {
+                let (target, rate) = if input != Vec2::ZERO {
+                    (input * max_speed, acceleration)
+                } else {
+                    (Vec2::ZERO, deceleration)
+                };
+                let change = target - motion.velocity;
+                motion.velocity + change.clamp_length_max(rate * delta_seconds)
+            }
+        };
+
+        if motion.velocity == Vec2::ZERO {
+            return;
         }
-        if keyboard.pressed(KeyCode::A) {
-            direction.x -= 1.0;
+
+        // Try to move in both directions independently, so the player slides along walls
+        let (new_pos, blocked) = slide_against_walls(
+            transform.translation,
+            motion.velocity * delta_seconds,
+            sprite.custom_size.unwrap_or(Vec2::ONE),
+            &navigation.walls,
+        );
+        transform.translation = new_pos;
+
+        // Only the velocity into the wall is lost; the tangential part carries on
+        if blocked.x {
+            motion.velocity.x = 0.0;
         }
-        if keyboard.pressed(KeyCode::D) {
-            direction.x += 1.0;
+        if blocked.y {
+            motion.velocity.y = 0.0;
         }
+    }
+}
 
-        if direction != Vec2::ZERO {
-            direction = direction.normalize();
-            
-            // Try to move in both directions independently
-            let mut new_pos = transform.translation;
-            let delta = PLAYER_SPEED * time.delta_seconds();
-            
-            // Try X movement
-            if direction.x != 0.0 {
-                let x_pos = new_pos + Vec3::new(direction.x * delta, 0.0, 0.0);
-                let mut x_blocked = false;
-                for (wall_transform, wall_sprite) in wall_query.iter() {
-                    if let Some(_) = collide(
-                        x_pos,
-                        sprite.custom_size.unwrap_or(Vec2::ONE),
-                        wall_transform.translation,
-                        wall_sprite.custom_size.unwrap_or(Vec2::ONE),
-                    ) {
-                        x_blocked = true;
-                        break;
-                    }
-                }
-                if !x_blocked {
-                    new_pos = x_pos;
-                }
-            }
-            
-            // Try Y movement
-            if direction.y != 0.0 {
-                let y_pos = new_pos + Vec3::new(0.0, direction.y * delta, 0.0);
-                let mut y_blocked = false;
-                for (wall_transform, wall_sprite) in wall_query.iter() {
-                    if let Some(_) = collide(
-                        y_pos,
-                        sprite.custom_size.unwrap_or(Vec2::ONE),
-                        wall_transform.translation,
-                        wall_sprite.custom_size.unwrap_or(Vec2::ONE),
-                    ) {
-                        y_blocked = true;
-                        break;
-                    }
-                }
-                if !y_blocked {
-                    new_pos = y_pos;
+pub fn start_dash(
+    mut commands: Commands,
+    mut player_query: Query<
+        (Entity, &PlayerMotion, &mut DashCooldown, Option<&mut Invulnerable>, Has<Knockback>),
+        (With<Player>, Without<Dashing>),
+    >,
+    action_state: Res<ActionState>,
+    time: Res<Time>,
+) {
+    if let Ok((entity, motion, mut cooldown, invulnerable, knocked_back)) = player_query.get_single_mut() {
+        cooldown.timer.tick(time.delta());
+        if action_state.just_pressed(Action::Dash) && cooldown.timer.finished() && !knocked_back {
+            cooldown.timer.reset();
+            commands.entity(entity).insert(Dashing::new(motion.facing));
+            // Keep a longer window left over from a hit or a broken shield
+            match invulnerable {
+                Some(mut invulnerable) => invulnerable.extend(DASH_INVULNERABLE_SECS),
+                None => {
+                    commands.entity(entity).insert(Invulnerable::new(DASH_INVULNERABLE_SECS));
                 }
             }
-            
-            transform.translation = new_pos;
         }
     }
 }
 
-pub fn spawn_coins(
+pub fn dash_movement(
     mut commands: Commands,
-    room_query: Query<(&Room, &Transform)>,
-    game_assets: Res<GameAssets>,
+    mut player_query: Query<(Entity, &mut Transform, &mut Dashing, &Sprite), With<Player>>,
+    navigation: Res<RoomNavigation>,
+    time: Res<Time>,
 ) {
-    let mut rng = rand::thread_rng();
-    if rng.gen_bool(COIN_SPAWN_CHANCE as f64) {
-        for (room, _) in room_query.iter() {
-            let random_position = Vec2::new(
-                rng.gen_range(-ROOM_SIZE/2.0 + 50.0..ROOM_SIZE/2.0 - 50.0),
-                rng.gen_range(-ROOM_SIZE/2.0 + 50.0..ROOM_SIZE/2.0 - 50.0),
-            );
+    if let Ok((entity, mut transform, mut dashing, sprite)) = player_query.get_single_mut() {
+        let size = sprite.custom_size.unwrap_or(Vec2::ONE);
+
+        // Never overshoot the dash distance on the last frame
+        let remaining = dashing.timer.remaining_secs();
+        let step = time.delta_seconds().min(remaining);
+        dashing.timer.tick(time.delta());
 
+        let delta = dashing.direction * DASH_DISTANCE / DASH_DURATION * step;
+        let (new_pos, blocked) = slide_against_walls(transform.translation, delta, size, &navigation.walls);
+        transform.translation = new_pos;
+
+        // Leave afterimages along the way, just behind the player
+        if dashing.trail_timer.tick(time.delta()).just_finished() {
             commands.spawn((
                 SpriteBundle {
-                    texture: game_assets.coin_sprite.clone(),
+                    transform: Transform::from_translation(new_pos.truncate().extend(0.5)),
                     sprite: Sprite {
-                        custom_size: Some(COIN_SIZE),
-                        color: Color::rgba(1.0, 1.0, 1.0, 0.5), // 50% opacity
+                        color: DASH_TRAIL_COLOR,
+                        custom_size: Some(size),
                         ..default()
                     },
-                    transform: Transform::from_translation(
-                        (room.position + random_position).extend(1.0)
-                    ),
                     ..default()
                 },
-                Coin {
-                    velocity: Vec2::ZERO,
-                    stationary_timer: Timer::new(Duration::from_secs(COIN_STATIONARY_TIME as u64), TimerMode::Once),
-                },
+                DashTrail::new(),
                 Cleanup,
             ));
         }
+
+        // The dash ends early if it runs into a wall
+        if dashing.timer.finished() || blocked.any() {
+            commands.entity(entity).remove::<Dashing>();
+        }
     }
 }
 
-pub fn handle_coin_state_change(
-    mut coin_query: Query<(&mut Coin, &mut Sprite)>,
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable)>,
     time: Res<Time>,
 ) {
-    let mut rng = rand::thread_rng();
-    
-    for (mut coin, mut sprite) in coin_query.iter_mut() {
-        if coin.stationary_timer.tick(time.delta()).just_finished() {
-            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
-            coin.velocity = Vec2::new(angle.cos(), angle.sin()) * COIN_SPEED;
-            sprite.color.set_a(1.0); // Full opacity when moving
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
         }
     }
 }
 
-pub fn coin_movement(
-    mut param_set: ParamSet<(
-        Query<(&Transform, &Sprite), With<Wall>>,
-        Query<(&mut Transform, &mut Coin)>,
-    )>,
+pub fn fade_dash_trail(
+    mut commands: Commands,
+    mut trail_query: Query<(Entity, &mut DashTrail, &mut Sprite)>,
     time: Res<Time>,
 ) {
-    // First, collect all wall data
-    let wall_data: Vec<(Vec3, Vec2)> = param_set.p0()
-        .iter()
-        .map(|(transform, sprite)| (
-            transform.translation,
-            sprite.custom_size.unwrap_or(Vec2::ONE)
-        ))
-        .collect();
-
-    // Then update coins
-    let mut coin_query = param_set.p1();
-    for (mut transform, mut coin) in coin_query.iter_mut() {
-        if coin.velocity != Vec2::ZERO {
-            let new_pos = transform.translation + (coin.velocity * time.delta_seconds()).extend(0.0);
-            
-            // Check wall collisions
-            let mut should_update = true;
-            
-            for (wall_pos, wall_size) in wall_data.iter() {
-                if let Some(collision) = collide(
-                    new_pos,
-                    COIN_SIZE,
-                    *wall_pos,
-                    *wall_size,
-                ) {
-                    match collision {
-                        Collision::Left | Collision::Right => coin.velocity.x *= -1.0,
-                        Collision::Top | Collision::Bottom => coin.velocity.y *= -1.0,
-                        Collision::Inside => should_update = false,
-                    }
-                    break;
-                }
-            }
-            
-            if should_update {
-                transform.translation = new_pos;
-            }
+    for (entity, mut trail, mut sprite) in trail_query.iter_mut() {
+        if trail.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else {
+            sprite.color.set_a(DASH_TRAIL_COLOR.a() * trail.timer.percent_left());
         }
     }
 }
 
-pub fn check_coin_collision(
-    player_query: Query<(&Transform, &Sprite), With<Player>>,
-    coin_query: Query<(&Transform, &Sprite, &Coin), With<Coin>>,
-    mut next_state: ResMut<NextState<GameState>>,
-    game_state: Res<State<GameState>>,
+pub fn spawn_coins(
+    mut commands: Commands,
+    room_query: Query<(&Room, &Transform)>,
+    player_query: Query<&Transform, With<Player>>,
+    coin_query: Query<&Transform, With<Coin>>,
+    game_assets: Res<GameAssets>,
+    coin_archetypes: Res<CoinArchetypes>,
+    game_timer: Res<GameTimer>,
+    spawn_rules: Res<SpawnRules>,
+    current_room: Res<CurrentRoom>,
+    user_settings: Res<UserSettings>,
 ) {
-    // Only check collisions if we're in Playing state
-    if *game_state.get() == GameState::Playing {
-        if let Ok((player_transform, player_sprite)) = player_query.get_single() {
-            for (coin_transform, coin_sprite, coin) in coin_query.iter() {
-                // Only check collision if coin is moving (not stationary)
-                if coin.velocity != Vec2::ZERO {
-                    if let Some(_) = collide(
-                        player_transform.translation,
-                        player_sprite.custom_size.unwrap_or(Vec2::ONE),
-                        coin_transform.translation,
-                        coin_sprite.custom_size.unwrap_or(Vec2::ONE),
-                    ) {
-                        next_state.set(GameState::GameOver);
-                        return;
-                    }
-                }
+    let mut rng = rand::thread_rng();
+    let spawn_chance = (COIN_SPAWN_CHANCE * user_settings.difficulty.spawn_rate()).min(1.0);
+    if rng.gen_bool(spawn_chance as f64) {
+        let Ok(player_transform) = player_query.get_single() else {
+            return;
+        };
+        let player_pos = player_transform.translation.truncate();
+
+        let available: Vec<&CoinArchetype> = coin_archetypes
+            .available(game_timer.difficulty())
+            .collect();
+        let Ok(weights) = WeightedIndex::new(available.iter().map(|archetype| archetype.spawn_weight)) else {
+            return;
+        };
+
+        let mut occupied: Vec<Vec2> = coin_query
+            .iter()
+            .map(|transform| transform.translation.truncate())
+            .collect();
+
+        // Only rooms around the player spawn coins; neighbours off screen get them early
+        // so coins can wander in through the passages
+        let active_rooms = room_query
+            .iter()
+            .filter(|(room, _)| in_active_ring(room.position, player_pos, spawn_rules.active_ring));
+
+        for (room, _) in active_rooms {
+            if in_entry_grace(room, &current_room, &spawn_rules, game_timer.elapsed_secs) {
+                continue;
             }
+            let Some(position) = pick_spawn_position(&mut rng, room, player_pos, &occupied, &spawn_rules) else {
+                continue;
+            };
+            let archetype = available[weights.sample(&mut rng)];
+            let launch_direction = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU));
+
+            spawn_coin(
+                &mut commands,
+                &game_assets,
+                archetype,
+                position,
+                launch_direction,
+                archetype.stationary_time,
+            );
+            occupied.push(position);
         }
     }
 }
 
-pub fn camera_follow(
+pub fn spawn_formations(
+    mut commands: Commands,
+    mut spawn_director: ResMut<SpawnDirector>,
+    room_query: Query<&Room>,
     player_query: Query<&Transform, With<Player>>,
-    mut camera_query: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
+    game_assets: Res<GameAssets>,
+    coin_archetypes: Res<CoinArchetypes>,
+    game_timer: Res<GameTimer>,
+    spawn_rules: Res<SpawnRules>,
+    current_room: Res<CurrentRoom>,
+    time: Res<Time>,
 ) {
-    if let Ok(player_transform) = player_query.get_single() {
-        if let Ok(mut camera_transform) = camera_query.get_single_mut() {
-            camera_transform.translation.x = player_transform.translation.x;
-            camera_transform.translation.y = player_transform.translation.y;
+    if !spawn_director.formation_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // Formations come more often as the difficulty rises
+    let interval = (FORMATION_INTERVAL_SECS - game_timer.difficulty() as f32 * FORMATION_INTERVAL_STEP)
+        .max(FORMATION_MIN_INTERVAL_SECS);
+    spawn_director.formation_timer.set_duration(Duration::from_secs_f32(interval));
+
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    let mut rng = rand::thread_rng();
+
+    let rooms: Vec<&Room> = room_query
+        .iter()
+        .filter(|room| in_active_ring(room.position, player_pos, spawn_rules.active_ring))
+        .filter(|room| !in_entry_grace(room, &current_room, &spawn_rules, game_timer.elapsed_secs))
+        .collect();
+    let Some(room) = rooms.choose(&mut rng) else {
+        return;
+    };
+
+    // The whole formation shares one coin kind so the pattern reads clearly
+    let available: Vec<&CoinArchetype> = coin_archetypes.available(game_timer.difficulty()).collect();
+    let Ok(archetype) = available.choose_weighted(&mut rng, |archetype| archetype.spawn_weight) else {
+        return;
+    };
+    let Some(&formation) = Formation::ALL.choose(&mut rng) else {
+        return;
+    };
+
+    for slot in formation_slots(formation, &mut rng) {
+        let position = room.position + slot.offset;
+        if position.distance(player_pos) < spawn_rules.min_player_distance {
+            continue;
         }
+
+        spawn_coin(
+            &mut commands,
+            &game_assets,
+            archetype,
+            position,
+            slot.direction,
+            archetype.stationary_time + slot.delay,
+        );
     }
 }
 
-pub fn check_room_generation(
-    player_query: Query<&Transform, With<Player>>,
-    room_query: Query<(&Room, &Transform)>,
+pub fn update_emitters(
     mut commands: Commands,
+    mut emitter_query: Query<(&mut Emitter, &mut Sprite, &GlobalTransform)>,
+    player_query: Query<&Transform, With<Player>>,
+    game_assets: Res<GameAssets>,
+    coin_archetypes: Res<CoinArchetypes>,
+    mut missing_projectiles: Local<HashSet<&'static str>>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
 ) {
-    if let Ok(player_transform) = player_query.get_single() {
-        let player_pos = player_transform.translation.truncate();
-        
-        // Find the current room
-        if let Some((current_room, _)) = room_query
-            .iter()
-            .find(|(room, _)| {
-                let room_bounds = ROOM_SIZE / 2.0;
-                let room_pos = room.position;
-                player_pos.x >= room_pos.x - room_bounds
-                    && player_pos.x <= room_pos.x + room_bounds
-                    && player_pos.y >= room_pos.y - room_bounds
-                    && player_pos.y <= room_pos.y + room_bounds
-            })
-        {
-            // Generate rooms recursively up to ROOM_GENERATION_DISTANCE
-            generate_rooms_recursive(&mut commands, current_room, &room_query, ROOM_GENERATION_DISTANCE);
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+
+    for (mut emitter, mut sprite, transform) in emitter_query.iter_mut() {
+        let emitter_pos = transform.translation().truncate();
+        if emitter_pos.distance(player_pos) > EMITTER_RANGE {
+            continue;
+        }
+
+        // Glow brighter as the next shot approaches
+        let remaining = emitter.fire_timer.remaining_secs();
+        let charge = 1.0 - (remaining / EMITTER_TELEGRAPH_SECS).min(1.0);
+        sprite.color = lerp_color(EMITTER_IDLE_COLOR, EMITTER_CHARGED_COLOR, charge);
+
+        if !emitter.fire_timer.tick(time.delta().mul_f32(time_scale.scale)).just_finished() {
+            continue;
+        }
+
+        let directions: Vec<Vec2> = match emitter.pattern {
+            EmitterPattern::Aimed { count, spread } => {
+                // With the player right on top of the emitter there is nothing to aim at
+                let Some(aim) = (player_pos - emitter_pos).try_normalize() else {
+                    continue;
+                };
+                (0..count)
+                    .map(|index| {
+                        let offset = if count > 1 {
+                            (index as f32 / (count - 1) as f32 - 0.5) * spread
+                        } else {
+                            0.0
+                        };
+                        Vec2::from_angle(offset).rotate(aim)
+                    })
+                    .collect()
+            }
+            EmitterPattern::Burst { count } => {
+                let start = emitter.shots as f32 * EMITTER_BURST_TURN;
+                (0..count)
+                    .map(|index| Vec2::from_angle(start + std::f32::consts::TAU * index as f32 / count as f32))
+                    .collect()
+            }
+        };
+        emitter.shots += 1;
+
+        let Some(projectile) = coin_archetypes.get(emitter.projectile) else {
+            if missing_projectiles.insert(emitter.projectile) {
+                warn!("Emitters fire coin kind '{}', which is not defined in {COIN_DEFINITIONS_PATH}", emitter.projectile);
+            }
+            continue;
+        };
+
+        // Projectiles launch straight away; the emitter's glow was their telegraph
+        for direction in directions {
+            spawn_coin(&mut commands, &game_assets, projectile, emitter_pos, direction, 0.0);
         }
     }
 }
 
-fn generate_rooms_recursive(
-    commands: &mut Commands,
-    current_room: &Room,
-    room_query: &Query<(&Room, &Transform)>,
-    depth: i32,
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let (from, to) = (from.as_rgba_f32(), to.as_rgba_f32());
+    Color::rgba(
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+        from[2] + (to[2] - from[2]) * t,
+        from[3] + (to[3] - from[3]) * t,
+    )
+}
+
+pub fn spawn_chasers(
+    mut commands: Commands,
+    mut spawn_director: ResMut<SpawnDirector>,
+    room_query: Query<&Room>,
+    player_query: Query<&Transform, With<Player>>,
+    chaser_query: Query<(), With<Chaser>>,
+    game_timer: Res<GameTimer>,
+    spawn_rules: Res<SpawnRules>,
+    chaser_config: Res<ChaserConfig>,
+    time: Res<Time>,
 ) {
-    if depth <= 0 {
+    if game_timer.difficulty() < CHASER_MIN_DIFFICULTY
+        || !spawn_director.chaser_timer.tick(time.delta()).just_finished()
+        || chaser_query.iter().count() >= CHASER_MAX_ALIVE
+    {
         return;
     }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
 
-    for &exit in &current_room.exits {
-        let next_room_pos = current_room.position + exit.to_vec2() * ROOM_SIZE;
-        
-        // Check if room already exists at this position
-        if !room_query.iter().any(|(room, _)| room.position == next_room_pos) {
-            // Generate random exits for the new room
-            let mut rng = rand::thread_rng();
-            let num_exits = rng.gen_range(1..=4);
-            let mut available_exits = Direction::all();
-            available_exits.retain(|&dir| dir != exit.opposite()); // Ensure connection to previous room
-            available_exits.shuffle(&mut rng);
-            let mut new_exits = vec![exit.opposite()]; // Always include connection to previous room
-            new_exits.extend(available_exits.iter().take(num_exits - 1).cloned());
-            
-            let new_room = Room {
-                position: next_room_pos,
-                exits: new_exits.clone(),
-            };
-            
-            spawn_room(commands, next_room_pos, new_exits);
-            
-            // Recursively generate rooms from the new room
-            generate_rooms_recursive(commands, &new_room, room_query, depth - 1);
-        }
+    // Chasers appear in a neighbouring room so the player sees them coming
+    let player_cell = room_cell(player_pos);
+    let rooms: Vec<&Room> = room_query
+        .iter()
+        .filter(|room| room_cell(room.position) != player_cell)
+        .filter(|room| in_active_ring(room.position, player_pos, spawn_rules.active_ring))
+        .collect();
+    let Some(room) = rooms.choose(&mut rand::thread_rng()) else {
+        return;
+    };
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: CHASER_COLOR,
+                custom_size: Some(CHASER_SIZE),
+                ..default()
+            },
+            transform: Transform::from_translation(room.position.extend(1.0)),
+            ..default()
+        },
+        Chaser::new(chaser_config.speed, chaser_config.aggression),
+        Cleanup,
+    ));
+}
+
+// Rebuilds the room graph and wall index, only on frames where rooms came or went
+pub fn update_room_navigation(
+    mut navigation: ResMut<RoomNavigation>,
+    added_rooms: Query<(), Added<Room>>,
+    mut removed_rooms: RemovedComponents<Room>,
+    room_query: Query<&Room>,
+    wall_query: Query<(&Transform, &Sprite), With<Wall>>,
+) {
+    let rooms_removed = removed_rooms.read().count() > 0;
+    if added_rooms.is_empty() && !rooms_removed {
+        return;
     }
+
+    navigation.graph = RoomGraph::new(room_query.iter().map(|room| (room_cell(room.position), room.exits.as_slice())));
+    navigation.walls = WallIndex::new(wall_query
+        .iter()
+        .map(|(transform, sprite)| (transform.translation, sprite.custom_size.unwrap_or(Vec2::ONE)))
+        .collect());
 }
 
-pub fn despawn_invisible_coins(
-    mut commands: Commands,
-    windows: Query<&Window>,
-    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    coin_query: Query<(Entity, &Transform), With<Coin>>,
+pub fn chaser_movement(
+    mut chaser_query: Query<(&mut Transform, &mut Chaser)>,
+    player_query: Query<&Transform, (With<Player>, Without<Chaser>)>,
+    navigation: Res<RoomNavigation>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
 ) {
-    // Get the camera and window
-    let (_camera, camera_transform) = camera_query.single();
-    let window = windows.single();
+    if chaser_query.is_empty() {
+        return;
+    }
+    // Chasers slow down and freeze along with the coins
+    let delta = time.delta().mul_f32(time_scale.scale);
+    let delta_seconds = delta.as_secs_f32();
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    let player_cell = room_cell(player_pos);
 
-    // Calculate visible area in world coordinates
-    let window_size = Vec2::new(window.width(), window.height());
-    let visible_area_half = window_size / 2.0 + Vec2::splat(VISIBILITY_BUFFER);
+    for (mut transform, mut chaser) in chaser_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let cell = room_cell(position);
 
-    // Get camera position in world coordinates
-    let camera_pos = camera_transform.translation().truncate();
+        if chaser.repath_timer.tick(delta).just_finished() || chaser.path.first() == Some(&cell) {
+            chaser.path = navigation.graph.find_path(cell, player_cell).unwrap_or_default();
+        }
 
-    // Check each coin
-    for (entity, transform) in coin_query.iter() {
-        let coin_pos = transform.translation.truncate();
-        let offset_from_camera = coin_pos - camera_pos;
+        // Head for the passage into the next room on the path, or straight for the
+        // player once in the same room or when no route is known
+        let target = match chaser.path.first() {
+            Some(&next) if cell != player_cell => {
+                let direction = (next - cell).as_vec2();
+                (cell.as_vec2() + direction * 0.5) * ROOM_SIZE + direction * CHASER_PASSAGE_LEAD
+            }
+            _ => player_pos,
+        };
 
-        // If coin is outside visible area, despawn it
-        if offset_from_camera.x.abs() > visible_area_half.x || 
-           offset_from_camera.y.abs() > visible_area_half.y {
-            commands.entity(entity).despawn();
+        let desired = (target - position).normalize_or_zero() * chaser.speed;
+        let steering = (chaser.aggression * CHASER_STEERING * delta_seconds).min(1.0);
+        chaser.velocity = chaser.velocity.lerp(desired, steering);
+
+        (transform.translation, _) = slide_against_walls(
+            transform.translation,
+            chaser.velocity * delta_seconds,
+            CHASER_SIZE,
+            &navigation.walls,
+        );
+    }
+}
+
+pub fn check_chaser_collision(
+    player_query: Query<(&Transform, &Sprite), (With<Player>, Without<Invulnerable>)>,
+    chaser_query: Query<(&Transform, &Sprite), With<Chaser>>,
+    mut hit_events: EventWriter<PlayerHit>,
+    time_scale: Res<CoinTimeScale>,
+) {
+    // Frozen chasers are harmless
+    if time_scale.scale == 0.0 {
+        return;
+    }
+    if let Ok((player_transform, player_sprite)) = player_query.get_single() {
+        for (chaser_transform, chaser_sprite) in chaser_query.iter() {
+            if collide(
+                player_transform.translation,
+                player_sprite.custom_size.unwrap_or(Vec2::ONE),
+                chaser_transform.translation,
+                chaser_sprite.custom_size.unwrap_or(Vec2::ONE),
+            ).is_some() {
+                hit_events.send(PlayerHit { source: chaser_transform.translation.truncate() });
+                return;
+            }
         }
     }
 }
 
-pub fn setup_ui(mut commands: Commands) {
-    // Timer text in bottom left
-    commands.spawn((
-        TextBundle::from_sections([
-            TextSection::new(
+pub fn spawn_coin(
+    commands: &mut Commands,
+    game_assets: &GameAssets,
+    archetype: &CoinArchetype,
+    position: Vec2,
+    launch_direction: Vec2,
+    stationary_time: f32,
+) -> Entity {
+    let mut coin = commands.spawn((
+        SpriteBundle {
+            texture: game_assets.coin_sprite.clone(),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(archetype.size)),
+                color: archetype.tint().with_a(0.5), // 50% opacity
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(1.0)),
+            ..default()
+        },
+        Coin {
+            velocity: Vec2::ZERO,
+            stationary_timer: Timer::new(Duration::from_secs_f32(stationary_time), TimerMode::Once),
+            speed: archetype.speed,
+            mass: archetype.mass,
+            launch_direction,
+        },
+        Cleanup,
+    ));
+
+    // Dotted line showing where the coin will launch, filled in by `update_coin_telegraphs`
+    let first_dot = archetype.size / 2.0 + TELEGRAPH_DOT_SPACING;
+    coin.with_children(|parent| {
+        for index in 0..TELEGRAPH_DOTS {
+            let offset = launch_direction * (first_dot + index as f32 * TELEGRAPH_DOT_SPACING);
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(TELEGRAPH_DOT_SIZE)),
+                        color: archetype.tint().with_a(TELEGRAPH_EMPTY_ALPHA),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(offset.extend(0.0)),
+                    ..default()
+                },
+                TelegraphDot { index },
+            ));
+        }
+    });
+
+    // Attach a behaviour component for every rule the archetype defines
+    coin.insert(CoinRoom { cell: room_cell(position) });
+    if archetype.room_bound {
+        coin.insert(RoomBound);
+    }
+    if let Some(remaining) = archetype.max_bounces {
+        coin.insert(BounceLimit { remaining });
+    }
+    if let Some(lifetime) = archetype.lifetime {
+        coin.insert(Lifetime {
+            timer: Timer::from_seconds(lifetime, TimerMode::Once),
+        });
+    }
+    if let Some(factor) = archetype.speedup_per_bounce {
+        coin.insert(BounceSpeedup { factor });
+    }
+    if let Some(steering) = archetype.steering {
+        coin.insert(Homing { steering });
+    }
+    if let Some(split) = archetype.split {
+        coin.insert(Splitting {
+            fragments: split.fragments,
+            generations: split.generations,
+        });
+    }
+    if let Some(angular_speed) = archetype.orbit {
+        coin.insert(Orbiting { angular_speed });
+    }
+
+    coin.id()
+}
+
+pub fn handle_coin_state_change(
+    mut commands: Commands,
+    mut coin_query: Query<(&mut Coin, &mut Sprite, Option<&Children>), Without<Sleeping>>,
+    dot_query: Query<Entity, With<TelegraphDot>>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    for (mut coin, mut sprite, children) in coin_query.iter_mut() {
+        if coin.stationary_timer.tick(time.delta().mul_f32(time_scale.scale)).just_finished() {
+            coin.velocity = coin.launch_direction * coin.speed;
+            sprite.color.set_a(1.0); // Full opacity when moving
+
+            // The telegraph has served its purpose once the coin is moving
+            for &child in children.into_iter().flatten() {
+                if dot_query.contains(child) {
+                    commands.entity(child).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+pub fn update_coin_telegraphs(
+    coin_query: Query<(&Coin, &Children)>,
+    mut dot_query: Query<(&TelegraphDot, &mut Sprite)>,
+) {
+    for (coin, children) in coin_query.iter() {
+        if coin.velocity != Vec2::ZERO {
+            continue;
+        }
+
+        // Dots light up one by one as the stationary timer runs out
+        let filled = coin.stationary_timer.percent() * TELEGRAPH_DOTS as f32;
+        for &child in children.iter() {
+            if let Ok((dot, mut sprite)) = dot_query.get_mut(child) {
+                let alpha = if (dot.index as f32) < filled { 1.0 } else { TELEGRAPH_EMPTY_ALPHA };
+                sprite.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+pub fn coin_movement(
+    mut coin_query: Query<(Entity, &mut Transform, &mut Coin, &Sprite), Without<Sleeping>>,
+    navigation: Res<RoomNavigation>,
+    mut bounce_events: EventWriter<CoinBounced>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut coin, sprite) in coin_query.iter_mut() {
+        if coin.velocity != Vec2::ZERO {
+            let step = step_coin(
+                transform.translation,
+                coin.velocity,
+                sprite.custom_size.unwrap_or(COIN_SIZE),
+                &navigation.walls,
+                time.delta_seconds() * time_scale.scale,
+            );
+
+            if step.bounced {
+                bounce_events.send(CoinBounced {
+                    coin: entity,
+                    position: transform.translation.truncate(),
+                });
+            }
+
+            coin.velocity = step.velocity;
+            transform.translation = step.position;
+        }
+    }
+}
+
+pub fn toggle_coin_collisions(
+    keyboard: Res<Input<KeyCode>>,
+    mut coin_physics: ResMut<CoinPhysics>,
+) {
+    if keyboard.just_pressed(KeyCode::F2) {
+        coin_physics.coin_collisions = !coin_physics.coin_collisions;
+    }
+}
+
+pub fn collide_coins(
+    mut coin_query: Query<(&mut Transform, &mut Coin, &Sprite), Without<Sleeping>>,
+    coin_physics: Res<CoinPhysics>,
+) {
+    if !coin_physics.coin_collisions {
+        return;
+    }
+
+    // Stationary coins stay put until they launch, so only moving coins collide
+    let mut moving: Vec<_> = coin_query
+        .iter_mut()
+        .filter(|(_, coin, _)| coin.velocity != Vec2::ZERO)
+        .collect();
+    let mut bodies: Vec<CoinBody> = moving
+        .iter()
+        .map(|(transform, coin, sprite)| CoinBody {
+            position: transform.translation.truncate(),
+            velocity: coin.velocity,
+            radius: sprite.custom_size.unwrap_or(COIN_SIZE).x / 2.0,
+            mass: coin.mass,
+        })
+        .collect();
+
+    if resolve_coin_collisions(&mut bodies, &mut SpatialGrid::new(COIN_GRID_CELL_SIZE)) == 0 {
+        return;
+    }
+
+    for ((transform, coin, _), body) in moving.iter_mut().zip(bodies) {
+        transform.translation = body.position.extend(transform.translation.z);
+        coin.velocity = body.velocity;
+    }
+}
+
+pub fn confine_coins_to_rooms(
+    room_query: Query<&Room>,
+    mut coin_query: Query<(Entity, &mut Transform, &mut Coin, &mut CoinRoom, &Sprite, Has<RoomBound>), Without<Sleeping>>,
+    mut bounce_events: EventWriter<CoinBounced>,
+) {
+    let room_cells: HashSet<IVec2> = room_query.iter().map(|room| room_cell(room.position)).collect();
+
+    for (entity, mut transform, mut coin, mut coin_room, sprite, room_bound) in coin_query.iter_mut() {
+        let cell = room_cell(transform.translation.truncate());
+        if cell == coin_room.cell {
+            continue;
+        }
+
+        // Open passages lead into the neighbouring room, unless it has not been generated
+        // yet or the coin is bound to its room, in which case the passage acts as a wall
+        if !room_bound && room_cells.contains(&cell) {
+            coin_room.cell = cell;
+            continue;
+        }
+
+        let size = sprite.custom_size.unwrap_or(COIN_SIZE);
+        (transform.translation, coin.velocity) =
+            bounce_off_room_edge(transform.translation, coin.velocity, size, coin_room.cell, cell);
+
+        bounce_events.send(CoinBounced {
+            coin: entity,
+            position: transform.translation.truncate(),
+        });
+    }
+}
+
+pub fn coin_homing(
+    player_query: Query<&Transform, With<Player>>,
+    mut coin_query: Query<(&Transform, &mut Coin, &Homing), (Without<Player>, Without<Sleeping>)>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        let player_pos = player_transform.translation.truncate();
+
+        for (transform, mut coin, homing) in coin_query.iter_mut() {
+            if coin.velocity == Vec2::ZERO {
+                continue;
+            }
+
+            // Turn toward the player by at most `steering` radians per second
+            let to_player = player_pos - transform.translation.truncate();
+            // Sitting right on the player there is no direction to turn toward
+            if to_player == Vec2::ZERO {
+                continue;
+            }
+            let angle = coin.velocity.angle_between(to_player);
+            let max_turn = homing.steering * time.delta_seconds() * time_scale.scale;
+            let turn = angle.clamp(-max_turn, max_turn);
+            coin.velocity = Vec2::from_angle(turn).rotate(coin.velocity);
+        }
+    }
+}
+
+pub fn coin_orbiting(
+    mut coin_query: Query<(&mut Coin, &Orbiting), Without<Sleeping>>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    for (mut coin, orbiting) in coin_query.iter_mut() {
+        if coin.velocity != Vec2::ZERO {
+            let turn = orbiting.angular_speed * time.delta_seconds() * time_scale.scale;
+            coin.velocity = Vec2::from_angle(turn).rotate(coin.velocity);
+        }
+    }
+}
+
+pub fn limit_coin_bounces(
+    mut commands: Commands,
+    mut bounce_events: EventReader<CoinBounced>,
+    mut coin_query: Query<&mut BounceLimit, Without<Fading>>,
+) {
+    for event in bounce_events.read() {
+        if let Ok(mut limit) = coin_query.get_mut(event.coin) {
+            if limit.remaining == 0 {
+                continue;
+            }
+            limit.remaining -= 1;
+            if limit.remaining == 0 {
+                commands.entity(event.coin).try_insert(Fading::new());
+            }
+        }
+    }
+}
+
+pub fn speed_up_on_bounce(
+    mut bounce_events: EventReader<CoinBounced>,
+    mut coin_query: Query<(&mut Coin, &BounceSpeedup)>,
+) {
+    for event in bounce_events.read() {
+        if let Ok((mut coin, speedup)) = coin_query.get_mut(event.coin) {
+            coin.velocity = (coin.velocity * speedup.factor).clamp_length_max(COIN_MAX_SPEED);
+        }
+    }
+}
+
+pub fn tick_coin_lifetimes(
+    mut commands: Commands,
+    mut coin_query: Query<(Entity, &Coin, &mut Lifetime), (Without<Fading>, Without<Sleeping>)>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    let delta = time.delta().mul_f32(time_scale.scale);
+    for (entity, coin, mut lifetime) in coin_query.iter_mut() {
+        // A coin's lifetime starts when it launches
+        if coin.velocity != Vec2::ZERO && lifetime.timer.tick(delta).just_finished() {
+            commands.entity(entity).try_insert(Fading::new());
+        }
+    }
+}
+
+pub fn fade_out_coins(
+    mut commands: Commands,
+    mut coin_query: Query<(Entity, &mut Fading, &mut Sprite), Without<Sleeping>>,
+    time_scale: Res<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    let delta = time.delta().mul_f32(time_scale.scale);
+    for (entity, mut fading, mut sprite) in coin_query.iter_mut() {
+        if fading.timer.tick(delta).finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite.color.set_a(fading.timer.percent_left());
+        }
+    }
+}
+
+pub fn split_coins(
+    mut commands: Commands,
+    mut bounce_events: EventReader<CoinBounced>,
+    coin_query: Query<(
+        &Coin,
+        &Splitting,
+        &Sprite,
+        Has<RoomBound>,
+        Option<&BounceLimit>,
+        Option<&Lifetime>,
+        Option<&BounceSpeedup>,
+        Option<&Homing>,
+        Option<&Orbiting>,
+    ), Without<Fading>>,
+    game_assets: Res<GameAssets>,
+) {
+    for event in bounce_events.read() {
+        let Ok((coin, splitting, sprite, room_bound, bounce_limit, lifetime, speedup, homing, orbiting)) =
+            coin_query.get(event.coin)
+        else {
+            continue;
+        };
+        // A coin that used its last bounce fades out instead of splitting
+        if bounce_limit.is_some_and(|limit| limit.remaining == 0) {
+            continue;
+        }
+
+        commands.entity(event.coin).despawn_recursive();
+
+        // Fan the fragments out around the reflected direction
+        let size = sprite.custom_size.unwrap_or(COIN_SIZE) * SPLIT_FRAGMENT_SCALE;
+        let fragments = splitting.fragments;
+        for i in 0..fragments {
+            let offset = if fragments > 1 {
+                (i as f32 / (fragments - 1) as f32 - 0.5) * SPLIT_SPREAD
+            } else {
+                0.0
+            };
+
+            let velocity = Vec2::from_angle(offset).rotate(coin.velocity);
+
+            // The timer is finished up front so the fragment launches immediately
+            let mut stationary_timer = Timer::new(Duration::ZERO, TimerMode::Once);
+            stationary_timer.tick(Duration::ZERO);
+
+            let mut fragment = commands.spawn((
+                SpriteBundle {
+                    texture: game_assets.coin_sprite.clone(),
+                    sprite: Sprite {
+                        custom_size: Some(size),
+                        color: sprite.color,
+                        ..default()
+                    },
+                    transform: Transform::from_translation(event.position.extend(1.0)),
+                    ..default()
+                },
+                Coin {
+                    velocity,
+                    stationary_timer,
+                    speed: coin.speed,
+                    mass: coin.mass * SPLIT_FRAGMENT_SCALE * SPLIT_FRAGMENT_SCALE,
+                    launch_direction: velocity.normalize_or_zero(),
+                },
+                CoinRoom { cell: room_cell(event.position) },
+                Cleanup,
+            ));
+
+            // Fragments carry on with whatever is left of the parent's behaviours
+            if room_bound {
+                fragment.insert(RoomBound);
+            }
+            if let Some(limit) = bounce_limit {
+                fragment.insert(BounceLimit { remaining: limit.remaining });
+            }
+            if let Some(lifetime) = lifetime {
+                fragment.insert(Lifetime { timer: lifetime.timer.clone() });
+            }
+            if let Some(speedup) = speedup {
+                fragment.insert(BounceSpeedup { factor: speedup.factor });
+            }
+            if let Some(homing) = homing {
+                fragment.insert(Homing { steering: homing.steering });
+            }
+            if let Some(orbiting) = orbiting {
+                fragment.insert(Orbiting { angular_speed: orbiting.angular_speed });
+            }
+            if splitting.generations > 1 {
+                fragment.insert(Splitting {
+                    fragments: splitting.fragments,
+                    generations: splitting.generations - 1,
+                });
+            }
+        }
+    }
+}
+
+pub fn check_coin_collision(
+    player_query: Query<(&Transform, &Sprite), (With<Player>, Without<Invulnerable>)>,
+    coin_query: Query<(&Transform, &Sprite, &Coin), Without<Fading>>,
+    mut hit_events: EventWriter<PlayerHit>,
+    game_state: Res<State<GameState>>,
+    time_scale: Res<CoinTimeScale>,
+) {
+    // Only check collisions if we're in Playing state; frozen coins are harmless
+    if *game_state.get() == GameState::Playing && time_scale.scale > 0.0 {
+        if let Ok((player_transform, player_sprite)) = player_query.get_single() {
+            for (coin_transform, coin_sprite, coin) in coin_query.iter() {
+                // Only check collision if coin is moving (not stationary) and not fading out
+                if coin.velocity != Vec2::ZERO && collide(
+                    player_transform.translation,
+                    player_sprite.custom_size.unwrap_or(Vec2::ONE),
+                    coin_transform.translation,
+                    coin_sprite.custom_size.unwrap_or(Vec2::ONE),
+                ).is_some() {
+                    hit_events.send(PlayerHit { source: coin_transform.translation.truncate() });
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub fn handle_player_hits(
+    mut commands: Commands,
+    mut hit_events: EventReader<PlayerHit>,
+    mut player_query: Query<(Entity, &Transform, &PlayerMotion, &mut Health, Has<Shield>), (With<Player>, Without<Invulnerable>)>,
+    mut combo: ResMut<Combo>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // Coins and chasers can both land a hit in the same frame; they count as one
+    let Some(hit) = hit_events.read().last() else {
+        return;
+    };
+    let Ok((player, transform, motion, mut health, shielded)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    // A shield takes the hit in place of a life, with a moment of invulnerability to get clear
+    if shielded {
+        commands.entity(player)
+            .remove::<Shield>()
+            .insert(Invulnerable::new(SHIELD_BREAK_INVULNERABLE_SECS));
+        return;
+    }
+
+    health.lives = health.lives.saturating_sub(1);
+    combo.multiplier = 1;
+    if health.lives == 0 {
+        next_state.set(GameState::GameOver);
+        return;
+    }
+
+    // Knock the player away from the hit, backwards if it came from dead centre
+    let away = (transform.translation.truncate() - hit.source).try_normalize().unwrap_or(-motion.facing);
+    commands.entity(player)
+        .remove::<Dashing>()
+        .insert((
+            Knockback::new(away * KNOCKBACK_SPEED),
+            Invulnerable::new(HIT_INVULNERABLE_SECS),
+        ));
+}
+
+pub fn detect_near_misses(
+    mut commands: Commands,
+    player_query: Query<(&Transform, &Sprite, Has<Invulnerable>), With<Player>>,
+    coin_query: Query<(Entity, &Transform, &Sprite, &Coin, Has<Grazing>, Has<NearMissed>), (Without<Fading>, Without<Sleeping>)>,
+    mut combo: ResMut<Combo>,
+    mut score: ResMut<Score>,
+    mut near_miss_events: EventWriter<NearMiss>,
+    time_scale: Res<CoinTimeScale>,
+) {
+    let Ok((player_transform, player_sprite, invulnerable)) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    let player_size = player_sprite.custom_size.unwrap_or(Vec2::ONE);
+
+    for (entity, transform, sprite, coin, grazing, near_missed) in coin_query.iter() {
+        let coin_pos = transform.translation.truncate();
+        let gap = box_gap(player_pos, player_size, coin_pos, sprite.custom_size.unwrap_or(COIN_SIZE));
+        let close = coin.velocity != Vec2::ZERO && gap <= NEAR_MISS_MARGIN;
+
+        // Walking past a frozen coin is no risk, so it scores nothing
+        match Grazing::change(close, grazing, near_missed, invulnerable, time_scale.scale == 0.0) {
+            GrazeChange::Start => {
+                commands.entity(entity).insert(Grazing);
+            }
+            GrazeChange::Cancel => {
+                commands.entity(entity).remove::<Grazing>();
+            }
+            GrazeChange::Score => {
+                commands.entity(entity).remove::<Grazing>().insert(NearMissed);
+                let multiplier = combo.multiplier;
+                let points = combo.near_miss(NEAR_MISS_POINTS);
+                score.points += points;
+                near_miss_events.send(NearMiss { position: coin_pos, points, multiplier });
+            }
+            GrazeChange::None => {}
+        }
+    }
+}
+
+pub fn decay_combo(mut combo: ResMut<Combo>, time: Res<Time>) {
+    combo.decay(time.delta());
+}
+
+// Pops the points up where the coin passed and starts the optional slow motion
+pub fn near_miss_feedback(
+    mut commands: Commands,
+    mut near_miss_events: EventReader<NearMiss>,
+    user_settings: Res<UserSettings>,
+    mut slow_motion: ResMut<SlowMotion>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    for event in near_miss_events.read() {
+        let label = if event.multiplier > 1 {
+            format!("+{} x{}", event.points, event.multiplier)
+        } else {
+            format!("+{}", event.points)
+        };
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 24.0,
+                        color: GOLD_COIN_COLOR,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_translation(event.position.extend(2.0)),
+                ..default()
+            },
+            FloatingText::new(),
+            Cleanup,
+        ));
+
+        if user_settings.assists.near_miss_slow_motion {
+            virtual_time.set_relative_speed(NEAR_MISS_SLOWMO_SPEED);
+            slow_motion.timer = Some(Timer::from_seconds(NEAR_MISS_SLOWMO_SECS, TimerMode::Once));
+        }
+    }
+}
+
+// Counted in real time so the slow motion does not stretch itself out
+pub fn end_slow_motion(
+    mut slow_motion: ResMut<SlowMotion>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    real_time: Res<Time<Real>>,
+) {
+    if let Some(timer) = slow_motion.timer.as_mut() {
+        if timer.tick(real_time.delta()).finished() {
+            slow_motion.timer = None;
+            virtual_time.set_relative_speed(1.0);
+        }
+    }
+}
+
+pub fn animate_floating_text(
+    mut commands: Commands,
+    mut text_query: Query<(Entity, &mut Transform, &mut Text, &mut FloatingText)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut text, mut floating) in text_query.iter_mut() {
+        if floating.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += FLOATING_TEXT_RISE / FLOATING_TEXT_SECS * time.delta_seconds();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(floating.timer.percent_left());
+        }
+    }
+}
+
+pub fn toggle_near_miss_slow_motion(
+    keyboard: Res<Input<KeyCode>>,
+    mut user_settings: ResMut<UserSettings>,
+) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        user_settings.assists.near_miss_slow_motion = !user_settings.assists.near_miss_slow_motion;
+    }
+}
+
+pub fn knockback_movement(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut Transform, &mut Knockback, &Sprite), With<Player>>,
+    navigation: Res<RoomNavigation>,
+    time: Res<Time>,
+) {
+    if let Ok((entity, mut transform, mut knockback, sprite)) = player_query.get_single_mut() {
+        (transform.translation, _) = slide_against_walls(
+            transform.translation,
+            knockback.velocity * time.delta_seconds(),
+            sprite.custom_size.unwrap_or(Vec2::ONE),
+            &navigation.walls,
+        );
+
+        if knockback.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
+
+// Blinks the player while they cannot be hit
+pub fn flash_invulnerable(
+    mut player_query: Query<(&mut Sprite, Option<&Invulnerable>), With<Player>>,
+    user_settings: Res<UserSettings>,
+) {
+    if let Ok((mut sprite, invulnerable)) = player_query.get_single_mut() {
+        let alpha = match invulnerable {
+            Some(_) if user_settings.assists.reduce_flashing => 0.5,
+            Some(invulnerable) => {
+                let flashes = (invulnerable.timer.elapsed_secs() / HIT_FLASH_INTERVAL) as u32;
+                if flashes.is_multiple_of(2) { 0.3 } else { 1.0 }
+            }
+            None => 1.0,
+        };
+        sprite.color.set_a(alpha);
+    }
+}
+
+// Scatters gold coins through newly generated rooms, inside their spawn zones
+pub fn place_gold_coins(
+    mut commands: Commands,
+    room_query: Query<(Entity, &Room), Added<Room>>,
+    game_assets: Res<GameAssets>,
+) {
+    let mut rng = rand::thread_rng();
+    let inner = ROOM_SIZE / 2.0 - SPAWN_MARGIN;
+
+    for (entity, room) in room_query.iter() {
+        let count = rng.gen_range(0..=GOLD_COINS_PER_ROOM);
+        commands.entity(entity).with_children(|parent| {
+            for _ in 0..count {
+                let zone = room.spawn_zones
+                    .choose(&mut rng)
+                    .copied()
+                    .unwrap_or(Rect::new(-inner, -inner, inner, inner));
+                let offset = Vec2::new(
+                    rng.gen_range(zone.min.x..zone.max.x),
+                    rng.gen_range(zone.min.y..zone.max.y),
+                );
+                parent.spawn((
+                    SpriteBundle {
+                        texture: game_assets.coin_sprite.clone(),
+                        sprite: Sprite {
+                            color: GOLD_COIN_COLOR,
+                            custom_size: Some(GOLD_COIN_SIZE),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(offset.extend(0.5)),
+                        ..default()
+                    },
+                    GoldCoin,
+                ));
+            }
+        });
+    }
+}
+
+// Gold coins pulse gently so they read differently from coins waiting to launch
+pub fn pulse_gold_coins(
+    mut gold_query: Query<&mut Transform, With<GoldCoin>>,
+    time: Res<Time>,
+) {
+    let scale = 1.0 + 0.15 * (time.elapsed_seconds() * 4.0).sin();
+    for mut transform in gold_query.iter_mut() {
+        transform.scale = Vec3::splat(scale);
+    }
+}
+
+pub fn collect_gold_coins(
+    mut commands: Commands,
+    player_query: Query<(&Transform, &Sprite), With<Player>>,
+    gold_query: Query<(Entity, &GlobalTransform), With<GoldCoin>>,
+    mut score: ResMut<Score>,
+) {
+    if let Ok((player_transform, player_sprite)) = player_query.get_single() {
+        for (entity, gold_transform) in gold_query.iter() {
+            if collide(
+                player_transform.translation,
+                player_sprite.custom_size.unwrap_or(Vec2::ONE),
+                gold_transform.translation(),
+                GOLD_COIN_SIZE,
+            ).is_some() {
+                commands.entity(entity).despawn_recursive();
+                score.points += GOLD_COIN_POINTS;
+            }
+        }
+    }
+}
+
+pub fn collect_power_ups(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &Transform, &mut Sprite), With<Player>>,
+    power_up_query: Query<(Entity, &GlobalTransform, &PowerUp)>,
+    mut coin_query: Query<(&Transform, &mut Coin), Without<Player>>,
+) {
+    let Ok((player, player_transform, mut player_sprite)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    for (entity, power_up_transform, power_up) in power_up_query.iter() {
+        if collide(
+            player_transform.translation,
+            player_sprite.custom_size.unwrap_or(Vec2::ONE),
+            power_up_transform.translation(),
+            POWERUP_SIZE,
+        ).is_none() {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        // Picking up an effect that is already running restarts its timer
+        match power_up.kind {
+            PowerUpKind::Shield => {
+                commands.entity(player).insert(Shield);
+            }
+            PowerUpKind::SlowTime => {
+                commands.entity(player).insert(SlowTime::new());
+            }
+            PowerUpKind::Shrink => {
+                player_sprite.custom_size = Some(PLAYER_SIZE * SHRINK_SCALE);
+                commands.entity(player).insert(Shrunk::new());
+            }
+            PowerUpKind::Repel => {
+                // Send nearby moving coins straight away from the player
+                let player_pos = player_transform.translation.truncate();
+                for (coin_transform, mut coin) in coin_query.iter_mut() {
+                    let offset = coin_transform.translation.truncate() - player_pos;
+                    if coin.velocity != Vec2::ZERO && offset.length() <= REPEL_RADIUS {
+                        let speed = coin.velocity.length().max(REPEL_SPEED);
+                        coin.velocity = offset.normalize_or_zero() * speed;
+                    }
+                }
+            }
+            PowerUpKind::Freeze => {
+                commands.entity(player).insert(Frozen::new());
+            }
+        }
+    }
+}
+
+pub fn tick_power_up_effects(
+    mut commands: Commands,
+    mut player_query: Query<(
+        Entity,
+        &Transform,
+        &mut Sprite,
+        Option<&mut SlowTime>,
+        Option<&mut Shrunk>,
+        Option<&mut Frozen>,
+    ), With<Player>>,
+    wall_query: Query<(&Transform, &Sprite), (With<Wall>, Without<Player>)>,
+    mut time_scale: ResMut<CoinTimeScale>,
+    time: Res<Time>,
+) {
+    let Ok((player, transform, mut sprite, slow_time, shrunk, frozen)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    let mut scale = 1.0;
+    if let Some(mut slow_time) = slow_time {
+        if slow_time.timer.tick(time.delta()).finished() {
+            commands.entity(player).remove::<SlowTime>();
+        } else {
+            scale = SLOW_TIME_SCALE;
+        }
+    }
+    if let Some(mut frozen) = frozen {
+        if frozen.timer.tick(time.delta()).finished() {
+            commands.entity(player).remove::<Frozen>();
+        } else {
+            scale = 0.0;
+        }
+    }
+    time_scale.scale = scale;
+
+    if let Some(mut shrunk) = shrunk {
+        // Wait until there is room to grow back, so the player never ends up inside a wall
+        let has_room = || !wall_query.iter().any(|(wall_transform, wall_sprite)| collide(
+            transform.translation,
+            PLAYER_SIZE,
+            wall_transform.translation,
+            wall_sprite.custom_size.unwrap_or(Vec2::ONE),
+        ).is_some());
+        if shrunk.timer.tick(time.delta()).finished() && has_room() {
+            sprite.custom_size = Some(PLAYER_SIZE);
+            commands.entity(player).remove::<Shrunk>();
+        }
+    }
+}
+
+pub fn toggle_trajectory_preview(
+    keyboard: Res<Input<KeyCode>>,
+    mut user_settings: ResMut<UserSettings>,
+) {
+    if keyboard.just_pressed(KeyCode::F1) {
+        user_settings.assists.trajectory_preview = !user_settings.assists.trajectory_preview;
+    }
+}
+
+pub fn draw_trajectory_preview(
+    mut gizmos: Gizmos,
+    user_settings: Res<UserSettings>,
+    player_query: Query<&Transform, With<Player>>,
+    room_query: Query<&Room>,
+    coin_query: Query<(
+        &Transform,
+        &Coin,
+        &Sprite,
+        &CoinRoom,
+        Has<RoomBound>,
+        Has<Splitting>,
+        Option<&BounceLimit>,
+        Option<&BounceSpeedup>,
+        Option<&Orbiting>,
+    ), (Without<Player>, Without<Homing>, Without<Fading>, Without<Sleeping>)>,
+    navigation: Res<RoomNavigation>,
+    time_scale: Res<CoinTimeScale>,
+) {
+    // Frozen coins have no path to show
+    if !user_settings.assists.trajectory_preview || time_scale.scale == 0.0 {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    let steps = (TRAJECTORY_PREVIEW_SECS / TRAJECTORY_PREVIEW_STEP).ceil() as usize;
+    // The preview covers a second of game time, so slowed coins draw a shorter path
+    let delta = TRAJECTORY_PREVIEW_STEP * time_scale.scale;
+    let room_cells: HashSet<IVec2> = room_query.iter().map(|room| room_cell(room.position)).collect();
+
+    // Homing coins are left out, since where they go depends on where the player goes. The rest
+    // follow the same steps as coin_orbiting, coin_movement, confine_coins_to_rooms and the bounce
+    // rules, and the path ends where the coin splits or starts to fade.
+    for (transform, coin, sprite, coin_room, room_bound, splitting, bounce_limit, speedup, orbiting) in coin_query.iter() {
+        let coin_pos = transform.translation.truncate();
+        if coin.velocity == Vec2::ZERO || coin_pos.distance(player_pos) > TRAJECTORY_PREVIEW_RANGE {
+            continue;
+        }
+
+        let size = sprite.custom_size.unwrap_or(COIN_SIZE);
+        let color = sprite.color.with_a(0.6);
+        let mut position = transform.translation;
+        let mut velocity = coin.velocity;
+        let mut cell = coin_room.cell;
+        let mut bounces_left = bounce_limit.map(|limit| limit.remaining);
+        let mut path = vec![coin_pos];
+
+        for _ in 0..steps {
+            if let Some(orbiting) = orbiting {
+                velocity = Vec2::from_angle(orbiting.angular_speed * delta).rotate(velocity);
+            }
+            let step = step_coin(position, velocity, size, &navigation.walls, delta);
+            (position, velocity) = (step.position, step.velocity);
+            let mut bounced = step.bounced;
+
+            let new_cell = room_cell(position.truncate());
+            if new_cell != cell {
+                if !room_bound && room_cells.contains(&new_cell) {
+                    cell = new_cell;
+                } else {
+                    (position, velocity) = bounce_off_room_edge(position, velocity, size, cell, new_cell);
+                    bounced = true;
+                }
+            }
+            path.push(position.truncate());
+
+            if bounced {
+                gizmos.circle_2d(position.truncate(), size.x / 2.0, color);
+                if let Some(speedup) = speedup {
+                    velocity = (velocity * speedup.factor).clamp_length_max(COIN_MAX_SPEED);
+                }
+                bounces_left = bounces_left.map(|left| left.saturating_sub(1));
+                if splitting || bounces_left == Some(0) {
+                    break;
+                }
+            }
+        }
+
+        gizmos.linestrip_2d(path, color);
+    }
+}
+
+pub fn toggle_minimap(
+    keyboard: Res<Input<KeyCode>>,
+    mut settings: ResMut<MinimapSettings>,
+) {
+    if keyboard.just_pressed(KeyCode::M) {
+        settings.visible = !settings.visible;
+    }
+    if keyboard.just_pressed(KeyCode::Tab) {
+        settings.full_screen = !settings.full_screen;
+    }
+}
+
+// Redraws the map whenever rooms are discovered, the player changes room or a power-up is taken
+pub fn draw_minimap(
+    mut commands: Commands,
+    mut map_query: Query<(Entity, &mut Style, &mut BackgroundColor), With<MinimapRoot>>,
+    room_query: Query<&Room>,
+    changed_rooms: Query<(), Changed<Room>>,
+    power_up_query: Query<(&PowerUp, &Parent)>,
+    mut collected: RemovedComponents<PowerUp>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    current_room: Res<CurrentRoom>,
+    settings: Res<MinimapSettings>,
+) {
+    let power_ups_taken = collected.read().count() > 0;
+    if changed_rooms.is_empty() && !current_room.is_changed() && !settings.is_changed() && !power_ups_taken {
+        return;
+    }
+    let Ok((map, mut style, mut background)) = map_query.get_single_mut() else {
+        return;
+    };
+    commands.entity(map).despawn_descendants();
+
+    if !settings.visible && !settings.full_screen {
+        style.display = Display::None;
+        return;
+    }
+    style.display = Display::Flex;
+
+    // Discovered rooms, plus generated rooms next to them as fog
+    let visited: HashMap<IVec2, &Room> = room_query
+        .iter()
+        .filter(|room| room.first_visited_at.is_some())
+        .map(|room| (room_cell(room.position), room))
+        .collect();
+    let fog: Vec<IVec2> = room_query
+        .iter()
+        .filter(|room| room.first_visited_at.is_none())
+        .map(|room| room_cell(room.position))
+        .filter(|cell| Direction::all().iter().any(|dir| visited.contains_key(&(*cell + dir.to_vec2().as_ivec2()))))
+        .collect();
+    let current = current_room.position.map(room_cell).unwrap_or(IVec2::ZERO);
+
+    let view = if settings.full_screen {
+        let window_size = window_query
+            .get_single()
+            .map(|window| Vec2::new(window.width(), window.height()))
+            .unwrap_or(Vec2::splat(MINIMAP_SIZE));
+        style.left = Val::Px(0.0);
+        style.top = Val::Px(0.0);
+        style.right = Val::Auto;
+        style.bottom = Val::Auto;
+        style.width = Val::Percent(100.0);
+        style.height = Val::Percent(100.0);
+        *background = BackgroundColor(MINIMAP_FULL_BACKGROUND);
+
+        // Leave a margin around the edge of the screen
+        let cells = visited.keys().copied().chain(fog.iter().copied());
+        MapView {
+            size: window_size,
+            ..MapView::fit(cells, window_size * 0.9, MINIMAP_FULL_MAX_CELL_SIZE)
+        }
+    } else {
+        style.left = Val::Auto;
+        style.top = Val::Auto;
+        style.right = Val::Px(10.0);
+        style.bottom = Val::Px(10.0);
+        style.width = Val::Px(MINIMAP_SIZE);
+        style.height = Val::Px(MINIMAP_SIZE);
+        *background = BackgroundColor(MINIMAP_BACKGROUND);
+        MapView::around(current, MINIMAP_CELL_SIZE, Vec2::splat(MINIMAP_SIZE))
+    };
+
+    commands.entity(map).with_children(|parent| {
+        let room_extent = Vec2::splat(MINIMAP_ROOM_FILL);
+        for &cell in &fog {
+            spawn_map_box(parent, view.rect(cell.as_vec2(), room_extent), MINIMAP_FOG_COLOR);
+        }
+
+        for (&cell, room) in &visited {
+            let color = if cell == current { MINIMAP_CURRENT_COLOR } else { MINIMAP_ROOM_COLOR };
+            spawn_map_box(parent, view.rect(cell.as_vec2(), room_extent), color);
+
+            // Exits bridge half of the gap toward the neighbouring cell
+            for &exit in &room.exits {
+                let direction = exit.to_vec2();
+                let half_gap = (1.0 - MINIMAP_ROOM_FILL) / 2.0;
+                let extent = if direction.x != 0.0 { Vec2::new(half_gap, 0.25) } else { Vec2::new(0.25, half_gap) };
+                let position = cell.as_vec2() + direction * (MINIMAP_ROOM_FILL / 2.0 + half_gap / 2.0);
+                spawn_map_box(parent, view.rect(position, extent), MINIMAP_ROOM_COLOR);
+            }
+
+            // The full-screen map notes how far into the run each room was first reached
+            if let (true, Some(visited_at)) = (settings.full_screen, room.first_visited_at) {
+                spawn_map_label(parent, view.rect(cell.as_vec2(), room_extent), format!("{visited_at:.0}s"));
+            }
+        }
+
+        if settings.show_power_ups {
+            for (power_up, room_entity) in power_up_query.iter() {
+                let Ok(room) = room_query.get(room_entity.get()) else {
+                    continue;
+                };
+                let cell = room_cell(room.position);
+                if visited.contains_key(&cell) {
+                    spawn_map_box(parent, view.rect(cell.as_vec2(), Vec2::splat(0.25)), power_up.kind.color());
+                }
+            }
+        }
+    });
+}
+
+fn spawn_map_box(parent: &mut ChildBuilder, rect: Rect, color: Color) {
+    parent.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(rect.min.x),
+            top: Val::Px(rect.min.y),
+            width: Val::Px(rect.width()),
+            height: Val::Px(rect.height()),
+            ..default()
+        },
+        background_color: BackgroundColor(color),
+        ..default()
+    });
+}
+
+fn spawn_map_label(parent: &mut ChildBuilder, rect: Rect, label: String) {
+    parent.spawn(TextBundle {
+        text: Text::from_section(
+            label,
+            TextStyle {
+                font_size: 14.0,
+                color: Color::BLACK,
+                ..default()
+            },
+        ),
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(rect.min.x + 2.0),
+            top: Val::Px(rect.min.y + 2.0),
+            ..default()
+        },
+        ..default()
+    });
+}
+
+pub fn track_current_room(
+    player_query: Query<&Transform, With<Player>>,
+    mut room_query: Query<&mut Room>,
+    game_timer: Res<GameTimer>,
+    mut current_room: ResMut<CurrentRoom>,
+    mut score: ResMut<Score>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        let player_pos = player_transform.translation.truncate();
+        let room_bounds = ROOM_SIZE / 2.0;
+
+        let room = room_query
+            .iter_mut()
+            .find(|room| (player_pos - room.position).abs().max_element() <= room_bounds);
+
+        if let Some(mut room) = room {
+            // Exploring pays, but only the first time through each room and not for the starting one
+            if room.first_visited_at.is_none() {
+                room.first_visited_at = Some(game_timer.elapsed_secs);
+                run_stats.visit_room(room_cell(room.position));
+                if current_room.position.is_some() {
+                    score.points += SCORE_PER_NEW_ROOM;
+                }
+            }
+
+            if current_room.position != Some(room.position) {
+                current_room.position = Some(room.position);
+                current_room.entered_at = game_timer.elapsed_secs;
+            }
+        }
+    }
+}
+
+pub fn camera_follow(
+    player_query: Query<&Transform, With<Player>>,
+    mut camera_query: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        if let Ok(mut camera_transform) = camera_query.get_single_mut() {
+            camera_transform.translation.x = player_transform.translation.x;
+            camera_transform.translation.y = player_transform.translation.y;
+        }
+    }
+}
+
+pub fn check_room_generation(
+    player_query: Query<&Transform, With<Player>>,
+    room_query: Query<(&Room, &Transform)>,
+    mut commands: Commands,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        let player_pos = player_transform.translation.truncate();
+        
+        // Find the current room
+        if let Some((current_room, _)) = room_query
+            .iter()
+            .find(|(room, _)| {
+                let room_bounds = ROOM_SIZE / 2.0;
+                let room_pos = room.position;
+                player_pos.x >= room_pos.x - room_bounds
+                    && player_pos.x <= room_pos.x + room_bounds
+                    && player_pos.y >= room_pos.y - room_bounds
+                    && player_pos.y <= room_pos.y + room_bounds
+            })
+        {
+            // Generate rooms recursively up to ROOM_GENERATION_DISTANCE
+            generate_rooms_recursive(&mut commands, current_room, &room_query, ROOM_GENERATION_DISTANCE);
+        }
+    }
+}
+
+fn generate_rooms_recursive(
+    commands: &mut Commands,
+    current_room: &Room,
+    room_query: &Query<(&Room, &Transform)>,
+    depth: i32,
+) {
+    if depth <= 0 {
+        return;
+    }
+
+    for &exit in &current_room.exits {
+        let next_room_pos = current_room.position + exit.to_vec2() * ROOM_SIZE;
+        
+        // Check if room already exists at this position
+        if !room_query.iter().any(|(room, _)| room.position == next_room_pos) {
+            // Generate random exits for the new room
+            let mut rng = rand::thread_rng();
+            let num_exits = rng.gen_range(1..=4);
+            let mut available_exits = Direction::all();
+            available_exits.retain(|&dir| dir != exit.opposite()); // Ensure connection to previous room
+            available_exits.shuffle(&mut rng);
+            let mut new_exits = vec![exit.opposite()]; // Always include connection to previous room
+            new_exits.extend(available_exits.iter().take(num_exits - 1).cloned());
+            let template = RoomTemplate::random(&mut rng);
+            
+            let new_room = Room {
+                position: next_room_pos,
+                exits: new_exits.clone(),
+                spawn_zones: template.spawn_zones(),
+                first_visited_at: None,
+            };
+            
+            spawn_room(commands, next_room_pos, new_exits, template);
+            
+            // Recursively generate rooms from the new room
+            generate_rooms_recursive(commands, &new_room, room_query, depth - 1);
+        }
+    }
+}
+
+pub fn update_coin_simulation_region(
+    mut commands: Commands,
+    player_query: Query<&Transform, With<Player>>,
+    coin_query: Query<(Entity, &Transform, Has<Sleeping>), With<Coin>>,
+    simulation_region: Res<SimulationRegion>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+
+    for (entity, transform, sleeping) in coin_query.iter() {
+        let coin_pos = transform.translation.truncate();
+
+        if in_active_ring(coin_pos, player_pos, simulation_region.active_ring) {
+            // Sleeping coins pick up exactly where they left off
+            if sleeping {
+                commands.entity(entity).remove::<Sleeping>();
+            }
+        } else if in_active_ring(coin_pos, player_pos, simulation_region.sleep_ring) {
+            if !sleeping {
+                commands.entity(entity).try_insert(Sleeping);
+            }
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn setup_ui(mut commands: Commands) {
+    // Timer text in bottom left
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
                 "Time: ",
                 TextStyle {
-                    font_size: 30.0,
-                    color: Color::WHITE,
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0.0",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        }),
+        TimerText,
+        Cleanup,
+    ));
+
+    // Score in the top right
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Score: ",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0",
+                TextStyle {
+                    font_size: 30.0,
+                    color: GOLD_COIN_COLOR,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            ..default()
+        }),
+        ScoreText,
+        Cleanup,
+    ));
+
+    // Rooms explored under the score
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Rooms: ",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(45.0),
+            ..default()
+        }),
+        RoomsText,
+        Cleanup,
+    ));
+
+    // Combo multiplier under the rooms explored, hidden until a combo is going
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 24.0,
+                color: GOLD_COIN_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(75.0),
+            ..default()
+        }),
+        ComboText,
+        Cleanup,
+    ));
+
+    // Lives above the dash cooldown
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Lives: ",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(75.0),
+            ..default()
+        }),
+        LivesText,
+        Cleanup,
+    ));
+
+    // Dash cooldown just above the timer
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Dash: ",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "Ready",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::GREEN,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(45.0),
+            ..default()
+        }),
+        DashText,
+        Cleanup,
+    ));
+
+    // Active power-up effects along the top left, hidden until picked up
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                top: Val::Px(10.0),
+                column_gap: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        },
+        Cleanup,
+    )).with_children(|parent| {
+        for kind in PowerUpKind::ALL {
+            // Repel acts the moment it is picked up, so it never shows as active
+            if kind == PowerUpKind::Repel {
+                continue;
+            }
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        display: Display::None,
+                        padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                        ..default()
+                    },
+                    background_color: BackgroundColor(kind.color()),
+                    ..default()
+                },
+                EffectIcon { kind },
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    kind.label(),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ));
+            });
+        }
+    });
+
+    // Map of discovered rooms, drawn by `draw_minimap`
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            ..default()
+        },
+        MinimapRoot,
+        Cleanup,
+    ));
+
+    // Game over text and restart button (hidden initially)
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(50.0),
+                top: Val::Percent(40.0),
+                margin: UiRect {
+                    left: Val::Px(-200.0), // Center the text by offsetting half its width
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(20.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        GameOverText,
+        Cleanup,
+    )).with_children(|parent| {
+        // Game Over text
+        parent.spawn(TextBundle::from_sections([
+            TextSection::new(
+                "Game Over!\n",
+                TextStyle {
+                    font_size: 50.0,
+                    color: Color::RED,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "You survived for ",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0.0",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                " seconds",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "\nScore: ",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0",
+                TextStyle {
+                    font_size: 30.0,
+                    color: GOLD_COIN_COLOR,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "\nRooms explored: ",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "\nFurthest from the start: ",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "0",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                " rooms",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+        ]));
+
+        // Best runs so far, filled in by `show_game_over`
+        parent.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            LeaderboardText,
+        ));
+
+        // Restart button
+        parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(150.0),
+                    height: Val::Px(50.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+                ..default()
+            },
+            ButtonAction::Restart,
+            DefaultButton,
+        )).with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Restart",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+    });
+}
+
+pub fn update_timer(
+    time: Res<Time>,
+    mut game_timer: ResMut<GameTimer>,
+    mut query: Query<&mut Text, With<TimerText>>,
+    game_state: Res<State<GameState>>,
+) {
+    if *game_state.get() == GameState::Playing {
+        game_timer.elapsed_secs += time.delta_seconds();
+        
+        // Update timer text
+        for mut text in query.iter_mut() {
+            text.sections[1].value = format!("{:.1}", game_timer.elapsed_secs);
+        }
+    }
+}
+
+pub fn update_score_hud(
+    score: Res<Score>,
+    game_timer: Res<GameTimer>,
+    mut text_query: Query<&mut Text, With<ScoreText>>,
+) {
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[1].value = score.total(game_timer.elapsed_secs).to_string();
+    }
+}
+
+pub fn update_rooms_hud(
+    run_stats: Res<RunStats>,
+    mut text_query: Query<&mut Text, With<RoomsText>>,
+) {
+    if run_stats.is_changed() {
+        if let Ok(mut text) = text_query.get_single_mut() {
+            text.sections[1].value = run_stats.rooms_explored.to_string();
+        }
+    }
+}
+
+pub fn update_combo_hud(
+    combo: Res<Combo>,
+    mut text_query: Query<&mut Text, With<ComboText>>,
+) {
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = if combo.multiplier > 1 {
+            format!("Combo x{}", combo.multiplier)
+        } else {
+            String::new()
+        };
+    }
+}
+
+pub fn update_lives_hud(
+    player_query: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut text_query: Query<&mut Text, With<LivesText>>,
+) {
+    if let (Ok(health), Ok(mut text)) = (player_query.get_single(), text_query.get_single_mut()) {
+        text.sections[1].value = health.lives.to_string();
+    }
+}
+
+pub fn update_dash_hud(
+    player_query: Query<&DashCooldown, With<Player>>,
+    mut text_query: Query<&mut Text, With<DashText>>,
+) {
+    if let (Ok(cooldown), Ok(mut text)) = (player_query.get_single(), text_query.get_single_mut()) {
+        let section = &mut text.sections[1];
+        if cooldown.timer.finished() {
+            section.value = "Ready".to_string();
+            section.style.color = Color::GREEN;
+        } else {
+            section.value = format!("{:.1}s", cooldown.timer.remaining_secs());
+            section.style.color = Color::GRAY;
+        }
+    }
+}
+
+pub fn update_effect_hud(
+    player_query: Query<(Has<Shield>, Option<&SlowTime>, Option<&Shrunk>, Option<&Frozen>), With<Player>>,
+    mut icon_query: Query<(&EffectIcon, &mut Style, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    let Ok((shielded, slow_time, shrunk, frozen)) = player_query.get_single() else {
+        return;
+    };
+
+    for (icon, mut style, children) in icon_query.iter_mut() {
+        // None when the effect is inactive; the shield has no duration to show
+        let remaining = match icon.kind {
+            PowerUpKind::Shield => shielded.then_some(None),
+            PowerUpKind::SlowTime => slow_time.map(|effect| Some(effect.timer.remaining_secs())),
+            PowerUpKind::Shrink => shrunk.map(|effect| Some(effect.timer.remaining_secs())),
+            PowerUpKind::Freeze => frozen.map(|effect| Some(effect.timer.remaining_secs())),
+            PowerUpKind::Repel => None,
+        };
+
+        style.display = if remaining.is_some() { Display::Flex } else { Display::None };
+        if let Some(&child) = children.first() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = match remaining.flatten() {
+                    Some(secs) => format!("{} {:.1}", icon.kind.label(), secs),
+                    None => icon.kind.label().to_string(),
+                };
+            }
+        }
+    }
+}
+
+pub fn show_game_over(
+    mut game_over_query: Query<(&mut Visibility, &Children), With<GameOverText>>,
+    mut text_query: Query<(&mut Text, Has<LeaderboardText>)>,
+    game_timer: Res<GameTimer>,
+    score: Res<Score>,
+    run_stats: Res<RunStats>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let final_score = score.total(game_timer.elapsed_secs);
+    let rank = leaderboard.submit(LeaderboardEntry {
+        score: final_score,
+        survived_secs: game_timer.elapsed_secs,
+    });
+
+    if let Ok((mut visibility, children)) = game_over_query.get_single_mut() {
+        *visibility = Visibility::Visible;
+        
+        // Fill in the final time and score, and the best runs with this one marked
+        for &child in children.iter() {
+            if let Ok((mut text, is_leaderboard)) = text_query.get_mut(child) {
+                if is_leaderboard {
+                    let mut lines = vec!["Best runs".to_string()];
+                    for (index, entry) in leaderboard.entries.iter().enumerate() {
+                        let marker = if Some(index) == rank { "  <" } else { "" };
+                        lines.push(format!(
+                            "{}. {} ({:.1}s){}",
+                            index + 1,
+                            entry.score,
+                            entry.survived_secs,
+                            marker,
+                        ));
+                    }
+                    text.sections[0].value = lines.join("\n");
+                } else if text.sections.len() >= 10 {
+                    text.sections[2].value = format!("{:.1}", game_timer.elapsed_secs);
+                    text.sections[5].value = final_score.to_string();
+                    text.sections[7].value = run_stats.rooms_explored.to_string();
+                    text.sections[9].value = run_stats.max_distance.to_string();
+                }
+            }
+        }
+    }
+}
+
+pub fn setup_menu(mut commands: Commands, game_mode: Res<GameMode>) {
+    // UI Camera
+    commands.spawn((
+        Camera2dBundle::default(),
+        MenuCleanup,
+    ));
+
+    // Root node
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: BackgroundColor(Color::rgb(0.1, 0.1, 0.1)),
+            ..default()
+        },
+        MenuUI,
+        MenuCleanup,
+    )).with_children(|parent| {
+        // Title
+        parent.spawn(TextBundle {
+            text: Text::from_section(
+                "DODGECOIN",
+                TextStyle {
+                    font_size: 80.0,
+                    color: Color::rgb(1.0, 0.7, 0.3), // Light orange
                     ..default()
                 },
             ),
-            TextSection::new(
-                "0.0",
+            style: Style {
+                margin: UiRect::all(Val::Px(50.0)),
+                ..default()
+            },
+            ..default()
+        });
+
+        // Start Button
+        parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(150.0),
+                    height: Val::Px(50.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(10.0)),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+                ..default()
+            },
+            ButtonAction::Start,
+            DefaultButton,
+        )).with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Start",
                 TextStyle {
-                    font_size: 30.0,
+                    font_size: 40.0,
                     color: Color::WHITE,
                     ..default()
                 },
-            ),
-        ])
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            left: Val::Px(10.0),
-            bottom: Val::Px(10.0),
-            ..default()
-        }),
-        Cleanup,
-    ));
+            ));
+        });
+
+        // Game mode button, cycles through the modes
+        parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(300.0),
+                    height: Val::Px(50.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(10.0)),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+                ..default()
+            },
+            ButtonAction::CycleMode,
+        )).with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    format!("Mode: {}", game_mode.name()),
+                    TextStyle {
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                ModeLabel,
+            ));
+        });
+
+        spawn_menu_button(parent, "Settings", ButtonAction::OpenSettings);
+
+        // Exit Button
+        parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(150.0),
+                    height: Val::Px(50.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(10.0)),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+                ..default()
+            },
+            ButtonAction::Exit,
+        )).with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Exit",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+    });
+}
+
+pub fn handle_buttons(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &ButtonAction), (Changed<Interaction>, With<Button>)>,
+    cleanup_query: Query<Entity, With<Cleanup>>,
+    game_state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut settings_return: ResMut<SettingsReturn>,
+    mut user_settings: ResMut<UserSettings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut game_timer: ResMut<GameTimer>,
+    mut game_mode: ResMut<GameMode>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, action) in &interaction_query {
+        match *interaction {
+            // Clicks go to the UI as usual, except while a new binding is being captured
+            Interaction::Pressed if rebinding.action.is_some() => {}
+            Interaction::Pressed => {
+                match action {
+                    ButtonAction::Start => {
+                        next_state.set(GameState::Playing);
+                    }
+                    ButtonAction::Exit => {
+                        exit.send(AppExit);
+                    }
+                    ButtonAction::Restart => {
+                        // Reset game timer
+                        game_timer.elapsed_secs = 0.0;
+                        // Clear out the old run, whether it ended or was paused, so setup starts afresh
+                        for entity in cleanup_query.iter() {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                        next_state.set(GameState::Playing);
+                    }
+                    ButtonAction::CycleMode => {
+                        *game_mode = game_mode.next();
+                    }
+                    ButtonAction::Resume => {
+                        next_state.set(GameState::Playing);
+                    }
+                    ButtonAction::QuitToMenu => {
+                        for entity in cleanup_query.iter() {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                        next_state.set(GameState::Menu);
+                    }
+                    ButtonAction::OpenSettings => {
+                        settings_return.state = *game_state.get();
+                        next_state.set(GameState::Settings);
+                    }
+                    ButtonAction::Back => {
+                        next_state.set(settings_return.state);
+                    }
+                    ButtonAction::ChangeSetting(kind) => {
+                        user_settings.cycle(*kind);
+                    }
+                    ButtonAction::Rebind(action) => {
+                        *rebinding = Rebinding { action: Some(*action), armed: false };
+                    }
+                    ButtonAction::ResetBindings => {
+                        *user_settings.controls.active_mut() = ActionMap::default();
+                    }
+                }
+            }
+            Interaction::Hovered | Interaction::None => {}
+        }
+    }
+}
+
+// Colours every button by its state; set only on change so untouched buttons keep their change ticks
+pub fn update_button_colors(
+    mut button_query: Query<(Entity, &Interaction, &mut BackgroundColor), With<ButtonAction>>,
+    menu_focus: Res<MenuFocus>,
+) {
+    for (entity, interaction, mut color) in button_query.iter_mut() {
+        let target = if menu_focus.button == Some(entity) {
+            Color::rgb(0.45, 0.45, 0.6) // Blue-grey when focused with a pad or keys
+        } else if *interaction == Interaction::None {
+            Color::rgb(0.3, 0.3, 0.3) // Default grey
+        } else {
+            Color::rgb(0.4, 0.4, 0.4) // Lighter grey when hovered
+        };
+        if color.0 != target {
+            color.0 = target;
+        }
+    }
+}
+
+pub fn toggle_pause(
+    action_state: Res<ActionState>,
+    rebinding: Res<Rebinding>,
+    game_state: Res<State<GameState>>,
+    settings_return: Res<SettingsReturn>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if action_state.just_pressed(Action::Pause) && rebinding.action.is_none() {
+        match game_state.get() {
+            GameState::Playing => next_state.set(GameState::Paused),
+            GameState::Paused => next_state.set(GameState::Playing),
+            GameState::Settings => next_state.set(settings_return.state),
+            _ => {}
+        }
+    }
+}
+
+// Pauses the game when the window loses focus, so alt-tabbing away never costs a life
+pub fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if focus_events.read().any(|event| !event.focused) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+// Turns this frame's raw input into action values through the active profile's bindings.
+// Each action takes the strongest of its bindings, from any connected gamepad.
+pub fn read_actions(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    user_settings: Res<UserSettings>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let map = user_settings.controls.active();
+    let binding_value = |binding: &Binding| match *binding {
+        Binding::Key(key) => if keyboard.pressed(key) { 1.0 } else { 0.0 },
+        Binding::Mouse(button) => if mouse.pressed(button) { 1.0 } else { 0.0 },
+        Binding::GamepadButton(button_type) => {
+            let pressed = gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type)));
+            if pressed { 1.0 } else { 0.0 }
+        }
+        Binding::GamepadAxis { axis, positive } => gamepads
+            .iter()
+            .filter_map(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+            .map(|value| if positive { value } else { -value })
+            .fold(0.0, f32::max),
+    };
+
+    let values = Action::ALL
+        .into_iter()
+        .map(|action| {
+            let value = map.bindings(action).iter().map(binding_value).fold(0.0, f32::max);
+            (action, value.min(1.0))
+        })
+        .collect();
+    action_state.update(values);
+}
+
+// Moves menu focus between the visible buttons with the move actions, so a pad's stick or
+// d-pad (or the arrow keys) can reach every button. The first press focuses the menu's
+// default button, and focus is dropped once its button is gone or hidden.
+pub fn navigate_menu_focus(
+    action_state: Res<ActionState>,
+    rebinding: Res<Rebinding>,
+    mut menu_focus: ResMut<MenuFocus>,
+    button_query: Query<(Entity, &GlobalTransform, &InheritedVisibility, Has<DefaultButton>), With<ButtonAction>>,
+) {
+    let visible = |(_, _, visibility, _): &(Entity, &GlobalTransform, &InheritedVisibility, bool)| visibility.get();
+    if let Some(focused) = menu_focus.button {
+        if !button_query.get(focused).is_ok_and(|button| visible(&button)) {
+            menu_focus.button = None;
+        }
+    }
+    if rebinding.action.is_some() {
+        return;
+    }
+
+    // UI y points down
+    let direction = [
+        (Action::MoveUp, Vec2::NEG_Y),
+        (Action::MoveDown, Vec2::Y),
+        (Action::MoveLeft, Vec2::NEG_X),
+        (Action::MoveRight, Vec2::X),
+    ]
+    .into_iter()
+    .find(|&(action, _)| action_state.just_pressed(action))
+    .map(|(_, direction)| direction);
+    let Some(direction) = direction else {
+        return;
+    };
+
+    let focused = menu_focus.button.and_then(|entity| button_query.get(entity).ok());
+    menu_focus.button = match focused {
+        Some((_, transform, _, _)) => {
+            let from = transform.translation().truncate();
+            let candidates = button_query
+                .iter()
+                .filter(visible)
+                .map(|(entity, transform, _, _)| (entity, transform.translation().truncate()));
+            nearest_in_direction(from, direction, candidates).or(menu_focus.button)
+        }
+        None => {
+            let mut buttons: Vec<_> = button_query.iter().filter(visible).collect();
+            buttons.sort_by(|a, b| a.1.translation().y.total_cmp(&b.1.translation().y));
+            buttons
+                .iter()
+                .find(|(_, _, _, is_default)| *is_default)
+                .or(buttons.first())
+                .map(|(entity, _, _, _)| *entity)
+        }
+    };
+}
+
+// Confirm presses the focused button, or the default button of whichever menu is showing
+pub fn confirm_menu_choice(
+    action_state: Res<ActionState>,
+    rebinding: Res<Rebinding>,
+    menu_focus: Res<MenuFocus>,
+    mut button_query: Query<(Entity, &mut Interaction, &InheritedVisibility, Has<DefaultButton>), With<ButtonAction>>,
+) {
+    if !action_state.just_pressed(Action::Confirm) || rebinding.action.is_some() {
+        return;
+    }
+    for (entity, mut interaction, visibility, is_default) in button_query.iter_mut() {
+        let chosen = match menu_focus.button {
+            Some(focused) => entity == focused,
+            None => is_default,
+        };
+        if chosen && visibility.get() {
+            *interaction = Interaction::Pressed;
+        }
+    }
+}
+
+// Adds the next input pressed to the action being rebound, or removes it if the action already has it.
+// Escape cancels, and the left mouse button is left to the UI.
+pub fn capture_rebinding(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut user_settings: ResMut<UserSettings>,
+) {
+    let Some(action) = rebinding.action else {
+        return;
+    };
+    // Skip the frame the capture started on, so the press that started it is not bound
+    if !rebinding.armed {
+        rebinding.armed = true;
+        return;
+    }
+    // Clicking cancels, which leaves every key free to be bound, Esc included
+    if mouse.just_pressed(MouseButton::Left) {
+        rebinding.action = None;
+        return;
+    }
+
+    let stick_axes = [
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+        GamepadAxisType::LeftZ,
+        GamepadAxisType::RightZ,
+    ];
+    let binding = keyboard
+        .get_just_pressed()
+        .next()
+        .map(|&key| Binding::Key(key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .find(|&&button| button != MouseButton::Left)
+                .map(|&button| Binding::Mouse(button))
+        })
+        .or_else(|| {
+            gamepad_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::GamepadButton(button.button_type))
+        })
+        .or_else(|| {
+            gamepads.iter().find_map(|gamepad| {
+                stick_axes.into_iter().find_map(|axis| {
+                    let value = gamepad_axes.get(GamepadAxis::new(gamepad, axis))?;
+                    (value.abs() >= INPUT_CAPTURE_THRESHOLD)
+                        .then_some(Binding::GamepadAxis { axis, positive: value > 0.0 })
+                })
+            })
+        });
+
+    if let Some(binding) = binding {
+        if !user_settings.controls.active_mut().toggle(action, binding) {
+            info!("Kept {} on {}, it is the last binding left", binding.label(), action.name());
+        }
+        rebinding.action = None;
+    }
+}
 
-    // Game over text and restart button (hidden initially)
+pub fn setup_pause_menu(mut commands: Commands) {
     commands.spawn((
         NodeBundle {
             style: Style {
                 position_type: PositionType::Absolute,
-                left: Val::Percent(50.0),
-                top: Val::Percent(40.0),
-                margin: UiRect {
-                    left: Val::Px(-200.0), // Center the text by offsetting half its width
-                    ..default()
-                },
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
                 flex_direction: FlexDirection::Column,
                 align_items: AlignItems::Center,
-                row_gap: Val::Px(20.0),
+                justify_content: JustifyContent::Center,
                 ..default()
             },
-            visibility: Visibility::Hidden,
+            background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+            z_index: ZIndex::Global(10),
             ..default()
         },
-        GameOverText,
-        Cleanup,
+        PauseMenu,
     )).with_children(|parent| {
-        // Game Over text
-        parent.spawn(TextBundle::from_sections([
-            TextSection::new(
-                "Game Over!\n",
-                TextStyle {
-                    font_size: 50.0,
-                    color: Color::RED,
-                    ..default()
-                },
-            ),
-            TextSection::new(
-                "You survived for ",
-                TextStyle {
-                    font_size: 30.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ),
-            TextSection::new(
-                "0.0",
-                TextStyle {
-                    font_size: 30.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ),
-            TextSection::new(
-                " seconds",
+        parent.spawn(TextBundle {
+            text: Text::from_section(
+                "Paused",
                 TextStyle {
-                    font_size: 30.0,
+                    font_size: 60.0,
                     color: Color::WHITE,
                     ..default()
                 },
             ),
-        ]));
-
-        // Restart button
-        parent.spawn((
-            ButtonBundle {
-                style: Style {
-                    width: Val::Px(150.0),
-                    height: Val::Px(50.0),
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
-                    margin: UiRect::all(Val::Px(20.0)),
-                    ..default()
-                },
-                background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+            style: Style {
+                margin: UiRect::all(Val::Px(30.0)),
                 ..default()
             },
-            ButtonAction::Restart,
-        )).with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Restart",
-                TextStyle {
-                    font_size: 40.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ));
+            ..default()
         });
+
+        spawn_menu_button(parent, "Resume", (ButtonAction::Resume, DefaultButton));
+        spawn_menu_button(parent, "Restart", ButtonAction::Restart);
+        spawn_menu_button(parent, "Settings", ButtonAction::OpenSettings);
+        spawn_menu_button(parent, "Quit to Menu", ButtonAction::QuitToMenu);
     });
 }
 
-pub fn update_timer(
-    time: Res<Time>,
-    mut game_timer: ResMut<GameTimer>,
-    mut query: Query<&mut Text>,
-    game_state: Res<State<GameState>>,
-) {
-    if *game_state.get() == GameState::Playing {
-        game_timer.elapsed_secs += time.delta_seconds();
-        
-        // Update timer text
-        for mut text in query.iter_mut() {
-            if text.sections.len() >= 2 {  // Make sure we have both sections
-                text.sections[1].value = format!("{:.1}", game_timer.elapsed_secs);
-            }
-        }
-    }
+// `action` is the button's ButtonAction, along with any other components it needs
+fn spawn_menu_button(parent: &mut ChildBuilder, label: &str, action: impl Bundle) {
+    parent.spawn((
+        ButtonBundle {
+            style: Style {
+                width: Val::Px(250.0),
+                height: Val::Px(50.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(10.0)),
+                margin: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+            ..default()
+        },
+        action,
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            label,
+            TextStyle {
+                font_size: 36.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        ));
+    });
 }
 
-pub fn show_game_over(
-    mut game_over_query: Query<(&mut Visibility, &Children), With<GameOverText>>,
-    mut text_query: Query<&mut Text>,
-    game_timer: Res<GameTimer>,
+pub fn cleanup_pause_menu(
+    mut commands: Commands,
+    query: Query<Entity, With<PauseMenu>>,
 ) {
-    if let Ok((mut visibility, children)) = game_over_query.get_single_mut() {
-        *visibility = Visibility::Visible;
-        
-        // Find and update the text with the final time
-        for &child in children.iter() {
-            if let Ok(mut text) = text_query.get_mut(child) {
-                if text.sections.len() >= 3 {
-                    text.sections[2].value = format!("{:.1}", game_timer.elapsed_secs);
-                    break;  // Exit after finding and updating the correct text
-                }
-            }
-        }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 }
 
-pub fn setup_menu(mut commands: Commands) {
-    // UI Camera
-    commands.spawn((
-        Camera2dBundle::default(),
-        MenuCleanup,
-    ));
+pub fn setup_settings_menu(
+    mut commands: Commands,
+    camera_query: Query<(), With<Camera>>,
+    user_settings: Res<UserSettings>,
+) {
+    // Coming from the main menu there is no camera left to draw the UI with
+    if camera_query.is_empty() {
+        commands.spawn((Camera2dBundle::default(), SettingsMenu));
+    }
 
-    // Root node
     commands.spawn((
         NodeBundle {
             style: Style {
+                position_type: PositionType::Absolute,
                 width: Val::Percent(100.0),
                 height: Val::Percent(100.0),
                 flex_direction: FlexDirection::Column,
@@ -665,119 +3050,225 @@ pub fn setup_menu(mut commands: Commands) {
                 ..default()
             },
             background_color: BackgroundColor(Color::rgb(0.1, 0.1, 0.1)),
+            z_index: ZIndex::Global(20),
             ..default()
         },
-        MenuUI,
-        MenuCleanup,
+        SettingsMenu,
     )).with_children(|parent| {
-        // Title
         parent.spawn(TextBundle {
             text: Text::from_section(
-                "DODGECOIN",
+                "Settings",
                 TextStyle {
-                    font_size: 80.0,
-                    color: Color::rgb(1.0, 0.7, 0.3), // Light orange
+                    font_size: 60.0,
+                    color: Color::WHITE,
                     ..default()
                 },
             ),
             style: Style {
-                margin: UiRect::all(Val::Px(50.0)),
+                margin: UiRect::all(Val::Px(20.0)),
                 ..default()
             },
             ..default()
         });
 
-        // Start Button
-        parent.spawn((
-            ButtonBundle {
-                style: Style {
-                    width: Val::Px(150.0),
-                    height: Val::Px(50.0),
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
-                    padding: UiRect::all(Val::Px(10.0)),
-                    margin: UiRect::all(Val::Px(10.0)),
-                    ..default()
-                },
-                background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
+        // General settings on the left, controls on the right
+        parent.spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::FlexStart,
+                column_gap: Val::Px(40.0),
                 ..default()
             },
-            ButtonAction::Start,
-        )).with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Start",
-                TextStyle {
-                    font_size: 40.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ));
+            ..default()
+        }).with_children(|parent| {
+            parent.spawn(settings_column()).with_children(|parent| {
+                for kind in SettingKind::ALL.into_iter().filter(|&kind| kind != SettingKind::ControlProfile) {
+                    spawn_setting_row(
+                        parent,
+                        kind.name(),
+                        ButtonAction::ChangeSetting(kind),
+                        (setting_text(user_settings.value_label(kind), 26.0), SettingValue { kind }),
+                        200.0,
+                    );
+                }
+            });
+
+            parent.spawn(settings_column()).with_children(|parent| {
+                let kind = SettingKind::ControlProfile;
+                spawn_setting_row(
+                    parent,
+                    kind.name(),
+                    ButtonAction::ChangeSetting(kind),
+                    (setting_text(user_settings.value_label(kind), 26.0), SettingValue { kind }),
+                    340.0,
+                );
+                // Pressing an action's button waits for an input to add to it, or remove if already bound
+                for action in Action::ALL {
+                    spawn_setting_row(
+                        parent,
+                        action.name(),
+                        ButtonAction::Rebind(action),
+                        (setting_text(binding_list(user_settings.controls.active(), action), 18.0), BindingLabel { action }),
+                        340.0,
+                    );
+                }
+                spawn_setting_row(
+                    parent,
+                    "Bindings",
+                    ButtonAction::ResetBindings,
+                    setting_text("Reset".to_string(), 26.0),
+                    340.0,
+                );
+            });
         });
 
-        // Exit Button
+        spawn_menu_button(parent, "Back", (ButtonAction::Back, DefaultButton));
+    });
+}
+
+fn settings_column() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn setting_text(value: String, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+}
+
+// A settings row: its name, and a button showing the value that changes it when pressed
+fn spawn_setting_row(
+    parent: &mut ChildBuilder,
+    name: &str,
+    action: ButtonAction,
+    value: impl Bundle,
+    button_width: f32,
+) {
+    parent.spawn(NodeBundle {
+        style: Style {
+            width: Val::Px(button_width + 300.0),
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn(setting_text(name.to_string(), 28.0));
         parent.spawn((
             ButtonBundle {
                 style: Style {
-                    width: Val::Px(150.0),
-                    height: Val::Px(50.0),
+                    width: Val::Px(button_width),
+                    height: Val::Px(40.0),
                     justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
-                    padding: UiRect::all(Val::Px(10.0)),
-                    margin: UiRect::all(Val::Px(10.0)),
+                    margin: UiRect::all(Val::Px(4.0)),
                     ..default()
                 },
                 background_color: BackgroundColor(Color::rgb(0.3, 0.3, 0.3)),
                 ..default()
             },
-            ButtonAction::Exit,
+            action,
         )).with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Exit",
-                TextStyle {
-                    font_size: 40.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ));
+            parent.spawn(value);
         });
     });
 }
 
-pub fn handle_buttons(
-    mut interaction_query: Query<
-        (&Interaction, &ButtonAction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut next_state: ResMut<NextState<GameState>>,
-    mut game_timer: ResMut<GameTimer>,
-    mut exit: EventWriter<AppExit>,
+fn binding_list(map: &ActionMap, action: Action) -> String {
+    let labels: Vec<String> = map.bindings(action).iter().map(|binding| binding.label()).collect();
+    if labels.is_empty() {
+        "Unbound".to_string()
+    } else {
+        labels.join(" / ")
+    }
+}
+
+pub fn update_setting_values(
+    user_settings: Res<UserSettings>,
+    mut value_query: Query<(&mut Text, &SettingValue)>,
 ) {
-    for (interaction, action, mut color) in &mut interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
-                match action {
-                    ButtonAction::Start => {
-                        next_state.set(GameState::Playing);
-                    }
-                    ButtonAction::Exit => {
-                        exit.send(AppExit);
-                    }
-                    ButtonAction::Restart => {
-                        // Reset game timer
-                        game_timer.elapsed_secs = 0.0;
-                        // Transition to Menu state (which will clean up everything)
-                        next_state.set(GameState::Menu);
-                        // Then immediately transition to Playing state
-                        next_state.set(GameState::Playing);
-                    }
-                }
-            }
-            Interaction::Hovered => {
-                *color = Color::rgb(0.4, 0.4, 0.4).into(); // Lighter grey when hovered
-            }
-            Interaction::None => {
-                *color = Color::rgb(0.3, 0.3, 0.3).into(); // Default grey
-            }
+    if user_settings.is_changed() {
+        for (mut text, value) in value_query.iter_mut() {
+            text.sections[0].value = user_settings.value_label(value.kind);
+        }
+    }
+}
+
+pub fn update_binding_labels(
+    user_settings: Res<UserSettings>,
+    rebinding: Res<Rebinding>,
+    mut label_query: Query<(&mut Text, &BindingLabel)>,
+) {
+    if !user_settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (mut text, label) in label_query.iter_mut() {
+        text.sections[0].value = if rebinding.action == Some(label.action) {
+            "Press an input (click cancels)".to_string()
+        } else {
+            binding_list(user_settings.controls.active(), label.action)
+        };
+    }
+}
+
+// Pushes display settings to the window, both at startup and whenever they change
+pub fn apply_settings(
+    user_settings: Res<UserSettings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    if !user_settings.is_changed() {
+        return;
+    }
+
+    if let Ok(mut window) = window_query.get_single_mut() {
+        let mode = user_settings.window_mode.window_mode();
+        if window.mode != mode {
+            window.mode = mode;
+        }
+        let present_mode = if user_settings.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync };
+        if window.present_mode != present_mode {
+            window.present_mode = present_mode;
+        }
+    }
+    ui_scale.0 = user_settings.ui_scale as f64;
+}
+
+pub fn save_settings(user_settings: Res<UserSettings>) {
+    // The initial load needs no saving
+    if user_settings.is_changed() && !user_settings.is_added() {
+        user_settings.save();
+    }
+}
+
+pub fn cleanup_settings_menu(
+    mut commands: Commands,
+    query: Query<Entity, With<SettingsMenu>>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    rebinding.action = None;
+}
+
+pub fn update_mode_label(
+    game_mode: Res<GameMode>,
+    mut label_query: Query<&mut Text, With<ModeLabel>>,
+) {
+    if game_mode.is_changed() {
+        for mut text in label_query.iter_mut() {
+            text.sections[0].value = format!("Mode: {}", game_mode.name());
         }
     }
 }
@@ -798,4 +3289,42 @@ pub fn cleanup_system(
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_keep_the_parents_bounce_limit_and_lifetime() {
+        let mut app = App::new();
+        app.add_event::<CoinBounced>()
+            .init_resource::<GameAssets>()
+            .add_systems(Update, split_coins);
+
+        let mut lifetime = Timer::from_seconds(4.0, TimerMode::Once);
+        lifetime.tick(Duration::from_secs(1));
+        let parent = app.world.spawn((
+            Sprite::default(),
+            Coin {
+                velocity: Vec2::new(100.0, 0.0),
+                stationary_timer: Timer::new(Duration::ZERO, TimerMode::Once),
+                speed: 100.0,
+                mass: 1.0,
+                launch_direction: Vec2::X,
+            },
+            Splitting { fragments: 2, generations: 1 },
+            BounceLimit { remaining: 2 },
+            Lifetime { timer: lifetime },
+        )).id();
+        app.world.resource_mut::<Events<CoinBounced>>().send(CoinBounced { coin: parent, position: Vec2::ZERO });
+        app.update();
+
+        let mut fragments = app.world.query::<(&Coin, &BounceLimit, &Lifetime)>();
+        let fragments: Vec<_> = fragments.iter(&app.world).collect();
+        assert_eq!(fragments.len(), 2);
+        for (_, limit, lifetime) in fragments {
+            assert_eq!(limit.remaining, 2);
+            assert_eq!(lifetime.timer.remaining_secs(), 3.0);
+        }
+    }
+}